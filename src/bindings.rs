@@ -0,0 +1,603 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use smithay::input::keyboard::{keysyms, Keysym, ModifiersState};
+
+use crate::window_manager::LayoutMessage;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    // Run through `sh -c`
+    Spawn(String),
+    Close,
+    FocusNext,
+    FocusPrevious,
+    CycleLayout,
+    SelectLayout(String),
+    Layout(LayoutMessage),
+    // Grows or shrinks the inner and outer gaps together
+    AdjustGaps(i32),
+    ToggleSmartGaps,
+    ToggleFloating,
+    ToggleMaximized,
+    Minimize,
+    RestoreMinimized,
+    SwitchWorkspace(usize),
+    MoveToWorkspace(usize),
+    ToggleView(usize),
+    ToggleTag(usize),
+    // Switch to a named binding mode, ExitMode goes back to the default one
+    EnterMode(String),
+    ExitMode,
+    // Reads the keyboard config again, see KeyboardConfig::load
+    ReloadKeyboardConfig,
+    NextKeyboardLayout,
+    PreviousKeyboardLayout,
+    Quit,
+}
+
+// The modifiers a binding cares about, lock keys are ignored
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        logo: false,
+    };
+    pub const SUPER: Modifiers = Modifiers {
+        logo: true,
+        ..Modifiers::NONE
+    };
+    pub const SUPER_SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ..Modifiers::SUPER
+    };
+    pub const SUPER_ALT: Modifiers = Modifiers {
+        alt: true,
+        ..Modifiers::SUPER
+    };
+    pub const SUPER_CTRL: Modifiers = Modifiers {
+        ctrl: true,
+        ..Modifiers::SUPER
+    };
+    pub const SUPER_CTRL_SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ..Modifiers::SUPER_CTRL
+    };
+}
+
+impl From<&ModifiersState> for Modifiers {
+    fn from(state: &ModifiersState) -> Self {
+        Self {
+            ctrl: state.ctrl,
+            alt: state.alt,
+            shift: state.shift,
+            logo: state.logo,
+        }
+    }
+}
+
+// Keysyms are the unshifted ones, so Super+Shift+1 is bound as 1 and not !
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub keysym: Keysym,
+}
+
+impl KeyCombo {
+    pub fn new(modifiers: Modifiers, keysym: Keysym) -> Self {
+        Self { modifiers, keysym }
+    }
+}
+
+pub const DEFAULT_MODE: &str = "default";
+
+// How long a started sequence waits for its next key
+pub const DEFAULT_CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A key either runs an action or starts a sequence with its own key table
+#[derive(Debug, Clone)]
+enum Binding {
+    Action(Action),
+    Prefix(HashMap<KeyCombo, Binding>),
+}
+
+// What a key press did to the bindings
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyResult {
+    Action(Action),
+    // The key continued a sequence
+    Pending,
+    // The key is not bound after the keys pressed so far, the sequence is dropped
+    Aborted,
+    Unbound,
+}
+
+// One key table per binding mode, only the table of the current mode is used
+pub struct Bindings {
+    modes: HashMap<String, HashMap<KeyCombo, Binding>>,
+    mode: String,
+    // The keys of the sequence typed so far and when the last one was pressed
+    pending: Vec<KeyCombo>,
+    pending_since: Option<Instant>,
+    chord_timeout: Duration,
+}
+
+impl Bindings {
+    pub fn empty() -> Self {
+        Self {
+            modes: HashMap::from([(DEFAULT_MODE.to_owned(), HashMap::new())]),
+            mode: DEFAULT_MODE.to_owned(),
+            pending: vec![],
+            pending_since: None,
+            chord_timeout: DEFAULT_CHORD_TIMEOUT,
+        }
+    }
+
+    pub fn bind(&mut self, combo: KeyCombo, action: Action) {
+        self.bind_in(DEFAULT_MODE, combo, action);
+    }
+
+    pub fn bind_in(&mut self, mode: &str, combo: KeyCombo, action: Action) {
+        self.bind_sequence_in(mode, &[combo], action);
+    }
+
+    pub fn bind_sequence(&mut self, sequence: &[KeyCombo], action: Action) {
+        self.bind_sequence_in(DEFAULT_MODE, sequence, action);
+    }
+
+    // Creates the mode if needed, new modes are left with Escape. A sequence
+    // replaces any binding on its prefixes and the other way around.
+    pub fn bind_sequence_in(&mut self, mode: &str, sequence: &[KeyCombo], action: Action) {
+        let Some((last, prefix)) = sequence.split_last() else {
+            return;
+        };
+        let mut table = self.modes.entry(mode.to_owned()).or_insert_with(|| {
+            let mut table = HashMap::new();
+            if mode != DEFAULT_MODE {
+                table.insert(
+                    KeyCombo::new(Modifiers::NONE, keysyms::KEY_Escape),
+                    Binding::Action(Action::ExitMode),
+                );
+            }
+            table
+        });
+        for combo in prefix {
+            let binding = table
+                .entry(*combo)
+                .or_insert_with(|| Binding::Prefix(HashMap::new()));
+            if let Binding::Action(_) = binding {
+                *binding = Binding::Prefix(HashMap::new());
+            }
+            let Binding::Prefix(next) = binding else {
+                unreachable!();
+            };
+            table = next;
+        }
+        table.insert(*last, Binding::Action(action));
+        self.reset_sequence();
+    }
+
+    pub fn unbind(&mut self, combo: KeyCombo) -> Option<Action> {
+        self.unbind_in(DEFAULT_MODE, combo)
+    }
+
+    pub fn unbind_in(&mut self, mode: &str, combo: KeyCombo) -> Option<Action> {
+        self.unbind_sequence_in(mode, &[combo])
+    }
+
+    pub fn unbind_sequence_in(&mut self, mode: &str, sequence: &[KeyCombo]) -> Option<Action> {
+        let (last, prefix) = sequence.split_last()?;
+        let mut table = self.modes.get_mut(mode)?;
+        for combo in prefix {
+            match table.get_mut(combo)? {
+                Binding::Prefix(next) => table = next,
+                Binding::Action(_) => return None,
+            }
+        }
+        let removed = table.remove(last)?;
+        self.reset_sequence();
+        match removed {
+            Binding::Action(action) => Some(action),
+            Binding::Prefix(_) => None,
+        }
+    }
+
+    pub fn mode(&self) -> &str {
+        &self.mode
+    }
+
+    pub fn modes(&self) -> impl Iterator<Item = &str> {
+        self.modes.keys().map(String::as_str)
+    }
+
+    // False if there is no such mode
+    pub fn set_mode(&mut self, mode: &str) -> bool {
+        if !self.modes.contains_key(mode) {
+            return false;
+        }
+        self.mode = mode.to_owned();
+        self.reset_sequence();
+        true
+    }
+
+    pub fn chord_timeout(&self) -> Duration {
+        self.chord_timeout
+    }
+
+    pub fn set_chord_timeout(&mut self, timeout: Duration) {
+        self.chord_timeout = timeout;
+    }
+
+    // The keys of the sequence in progress, if any
+    pub fn pending(&self) -> &[KeyCombo] {
+        &self.pending
+    }
+
+    fn reset_sequence(&mut self) {
+        self.pending.clear();
+        self.pending_since = None;
+    }
+
+    // The key table that continues the sequence typed so far
+    fn pending_table(&self) -> Option<&HashMap<KeyCombo, Binding>> {
+        let mut table = self.modes.get(&self.mode)?;
+        for combo in &self.pending {
+            match table.get(combo)? {
+                Binding::Prefix(next) => table = next,
+                Binding::Action(_) => return None,
+            }
+        }
+        Some(table)
+    }
+
+    // Drops the sequence in progress if nothing continued it within the timeout,
+    // true if there was one to drop
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.pending_since {
+            Some(since) if now.duration_since(since) >= self.chord_timeout => {
+                self.reset_sequence();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn press(&mut self, modifiers: &ModifiersState, keysyms: &[Keysym]) -> KeyResult {
+        self.expire(Instant::now());
+
+        // Modifiers are held down while typing a sequence, they don't end it
+        if keysyms.iter().all(|&keysym| is_modifier(keysym)) {
+            return KeyResult::Unbound;
+        }
+
+        let Some(table) = self.pending_table() else {
+            self.reset_sequence();
+            return KeyResult::Aborted;
+        };
+
+        let modifiers = Modifiers::from(modifiers);
+        let found = keysyms.iter().find_map(|&keysym| {
+            let combo = KeyCombo::new(modifiers, keysym);
+            table.get(&combo).map(|binding| (combo, binding))
+        });
+        match found {
+            Some((_, Binding::Action(action))) => {
+                let action = action.clone();
+                self.reset_sequence();
+                KeyResult::Action(action)
+            }
+            Some((combo, Binding::Prefix(_))) => {
+                self.pending.push(combo);
+                self.pending_since = Some(Instant::now());
+                KeyResult::Pending
+            }
+            None if self.pending.is_empty() => KeyResult::Unbound,
+            None => {
+                self.reset_sequence();
+                KeyResult::Aborted
+            }
+        }
+    }
+}
+
+// Level and group shifts like AltGr count too
+fn is_modifier(keysym: Keysym) -> bool {
+    (keysyms::KEY_Shift_L..=keysyms::KEY_Hyper_R).contains(&keysym)
+        || (keysyms::KEY_ISO_Lock..=keysyms::KEY_ISO_Level5_Lock).contains(&keysym)
+        || keysym == keysyms::KEY_Mode_switch
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let terminal = std::env::var("TERMINAL").unwrap_or_else(|_| "foot".into());
+
+        let mut bindings = Self::empty();
+        let mut bind =
+            |modifiers, keysym, action| bindings.bind(KeyCombo::new(modifiers, keysym), action);
+
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_Return,
+            Action::Spawn(terminal),
+        );
+        bind(Modifiers::SUPER, keysyms::KEY_q, Action::Close);
+        bind(Modifiers::SUPER, keysyms::KEY_j, Action::FocusNext);
+        bind(Modifiers::SUPER, keysyms::KEY_k, Action::FocusPrevious);
+        bind(Modifiers::SUPER, keysyms::KEY_space, Action::CycleLayout);
+        bind(
+            Modifiers::SUPER_SHIFT,
+            keysyms::KEY_space,
+            Action::ToggleFloating,
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_comma,
+            Action::Layout(LayoutMessage::MasterCount(1)),
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_period,
+            Action::Layout(LayoutMessage::MasterCount(-1)),
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_minus,
+            Action::Layout(LayoutMessage::MasterRatio(-0.05)),
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_equal,
+            Action::Layout(LayoutMessage::MasterRatio(0.05)),
+        );
+        bind(
+            Modifiers::SUPER_SHIFT,
+            keysyms::KEY_minus,
+            Action::Layout(LayoutMessage::ColumnWidth(-0.05)),
+        );
+        bind(
+            Modifiers::SUPER_SHIFT,
+            keysyms::KEY_equal,
+            Action::Layout(LayoutMessage::ColumnWidth(0.05)),
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_bracketleft,
+            Action::Layout(LayoutMessage::ConsumeIntoColumn),
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_bracketright,
+            Action::Layout(LayoutMessage::ExpelFromColumn),
+        );
+        bind(Modifiers::SUPER_ALT, keysyms::KEY_minus, Action::AdjustGaps(-2));
+        bind(Modifiers::SUPER_ALT, keysyms::KEY_equal, Action::AdjustGaps(2));
+        bind(Modifiers::SUPER_ALT, keysyms::KEY_s, Action::ToggleSmartGaps);
+        bind(Modifiers::SUPER, keysyms::KEY_m, Action::ToggleMaximized);
+        bind(Modifiers::SUPER, keysyms::KEY_n, Action::Minimize);
+        bind(
+            Modifiers::SUPER_SHIFT,
+            keysyms::KEY_n,
+            Action::RestoreMinimized,
+        );
+        bind(Modifiers::SUPER_SHIFT, keysyms::KEY_e, Action::Quit);
+        bind(
+            Modifiers::SUPER_SHIFT,
+            keysyms::KEY_c,
+            Action::ReloadKeyboardConfig,
+        );
+        bind(
+            Modifiers::SUPER_CTRL,
+            keysyms::KEY_space,
+            Action::NextKeyboardLayout,
+        );
+        bind(
+            Modifiers::SUPER_CTRL_SHIFT,
+            keysyms::KEY_space,
+            Action::PreviousKeyboardLayout,
+        );
+        bind(
+            Modifiers::SUPER,
+            keysyms::KEY_r,
+            Action::EnterMode("layout".into()),
+        );
+
+        let digits = [
+            keysyms::KEY_1,
+            keysyms::KEY_2,
+            keysyms::KEY_3,
+            keysyms::KEY_4,
+            keysyms::KEY_5,
+            keysyms::KEY_6,
+            keysyms::KEY_7,
+            keysyms::KEY_8,
+            keysyms::KEY_9,
+        ];
+        for (i, keysym) in digits.into_iter().enumerate() {
+            bind(Modifiers::SUPER, keysym, Action::SwitchWorkspace(i));
+            bind(Modifiers::SUPER_SHIFT, keysym, Action::MoveToWorkspace(i));
+            bind(Modifiers::SUPER_CTRL, keysym, Action::ToggleView(i));
+            bind(Modifiers::SUPER_CTRL_SHIFT, keysym, Action::ToggleTag(i));
+        }
+
+        // Super+l followed by a letter picks a layout
+        let layouts = [
+            (keysyms::KEY_t, "tile"),
+            (keysyms::KEY_m, "monocle"),
+            (keysyms::KEY_d, "dwindle"),
+            (keysyms::KEY_s, "scroll"),
+            (keysyms::KEY_f, "floating"),
+        ];
+        for (keysym, layout) in layouts {
+            bindings.bind_sequence(
+                &[
+                    KeyCombo::new(Modifiers::SUPER, keysyms::KEY_l),
+                    KeyCombo::new(Modifiers::NONE, keysym),
+                ],
+                Action::SelectLayout(layout.into()),
+            );
+        }
+
+        // Window arrangement without holding Super
+        let layout_mode = [
+            (keysyms::KEY_j, Action::FocusNext),
+            (keysyms::KEY_k, Action::FocusPrevious),
+            (keysyms::KEY_space, Action::CycleLayout),
+            (keysyms::KEY_f, Action::ToggleFloating),
+            (keysyms::KEY_m, Action::ToggleMaximized),
+            (keysyms::KEY_Return, Action::ExitMode),
+        ];
+        for (keysym, action) in layout_mode {
+            bindings.bind_in("layout", KeyCombo::new(Modifiers::NONE, keysym), action);
+        }
+
+        bindings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(logo: bool) -> ModifiersState {
+        ModifiersState {
+            logo,
+            ..Default::default()
+        }
+    }
+
+    // Super+l then t selects the tiling layout, like the default bindings
+    fn bindings() -> Bindings {
+        let mut bindings = Bindings::empty();
+        bindings.bind_sequence(
+            &[
+                KeyCombo::new(Modifiers::SUPER, keysyms::KEY_l),
+                KeyCombo::new(Modifiers::NONE, keysyms::KEY_t),
+            ],
+            Action::SelectLayout("tile".into()),
+        );
+        bindings.bind(
+            KeyCombo::new(Modifiers::SUPER, keysyms::KEY_q),
+            Action::Close,
+        );
+        bindings.bind_in(
+            "layout",
+            KeyCombo::new(Modifiers::NONE, keysyms::KEY_j),
+            Action::FocusNext,
+        );
+        bindings
+    }
+
+    #[test]
+    fn single_key_binding() {
+        let mut bindings = bindings();
+        assert_eq!(
+            bindings.press(&modifiers(true), &[keysyms::KEY_q]),
+            KeyResult::Action(Action::Close)
+        );
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_q]),
+            KeyResult::Unbound
+        );
+    }
+
+    #[test]
+    fn sequence_prefix_and_full_match() {
+        let mut bindings = bindings();
+        assert_eq!(
+            bindings.press(&modifiers(true), &[keysyms::KEY_l]),
+            KeyResult::Pending
+        );
+        assert_eq!(
+            bindings.pending(),
+            &[KeyCombo::new(Modifiers::SUPER, keysyms::KEY_l)]
+        );
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_t]),
+            KeyResult::Action(Action::SelectLayout("tile".into()))
+        );
+        assert!(bindings.pending().is_empty());
+    }
+
+    #[test]
+    fn sequence_mismatch_resets() {
+        let mut bindings = bindings();
+        bindings.press(&modifiers(true), &[keysyms::KEY_l]);
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_x]),
+            KeyResult::Aborted
+        );
+        assert!(bindings.pending().is_empty());
+        // The rest of the sequence alone is not bound
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_t]),
+            KeyResult::Unbound
+        );
+    }
+
+    #[test]
+    fn modifiers_keep_sequence() {
+        let mut bindings = bindings();
+        bindings.press(&modifiers(true), &[keysyms::KEY_l]);
+        for keysym in [
+            keysyms::KEY_Shift_L,
+            keysyms::KEY_ISO_Level3_Shift,
+            keysyms::KEY_ISO_Level5_Shift,
+            keysyms::KEY_Mode_switch,
+        ] {
+            assert_eq!(
+                bindings.press(&modifiers(false), &[keysym]),
+                KeyResult::Unbound
+            );
+        }
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_t]),
+            KeyResult::Action(Action::SelectLayout("tile".into()))
+        );
+    }
+
+    #[test]
+    fn sequence_expires() {
+        let mut bindings = bindings();
+        bindings.set_chord_timeout(Duration::from_millis(500));
+        assert!(!bindings.expire(Instant::now()));
+
+        bindings.press(&modifiers(true), &[keysyms::KEY_l]);
+        assert!(!bindings.expire(Instant::now()));
+        assert!(bindings.expire(Instant::now() + Duration::from_millis(500)));
+        assert!(bindings.pending().is_empty());
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_t]),
+            KeyResult::Unbound
+        );
+    }
+
+    #[test]
+    fn modes_have_their_own_tables() {
+        let mut bindings = bindings();
+        assert!(!bindings.set_mode("resize"));
+        assert!(bindings.set_mode("layout"));
+        assert_eq!(
+            bindings.press(&modifiers(true), &[keysyms::KEY_q]),
+            KeyResult::Unbound
+        );
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_j]),
+            KeyResult::Action(Action::FocusNext)
+        );
+        assert_eq!(
+            bindings.press(&modifiers(false), &[keysyms::KEY_Escape]),
+            KeyResult::Action(Action::ExitMode)
+        );
+    }
+}
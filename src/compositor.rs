@@ -1,6 +1,7 @@
 use smithay::{wayland::{compositor::{CompositorHandler, CompositorState, is_sync_subsurface, get_parent, with_states}, shell::xdg::XdgToplevelSurfaceData}, reexports::wayland_server::protocol::wl_surface::WlSurface, backend::renderer::utils::on_commit_buffer_handler, delegate_compositor};
+use tracing::trace;
 
-use crate::{state::State, drawing_backend::Backend};
+use crate::{state::State, drawing_backend::Backend, grabs};
 
 
 
@@ -16,21 +17,15 @@ impl<B: Backend> CompositorHandler for State<B> {
             while let Some(parent) = get_parent(&root) {
                 root = parent;
             }
-            if let Some(window) = self
-                .space
-                .elements()
-                .find(|w| w.toplevel().wl_surface() == &root)
-            {
+            // Windows on hidden tags or minimized ones are not in the space
+            if let Some(window) = self.wm.window_for_surface(&root) {
                 window.on_commit();
             }
         }
 
-        if let Some(window) = self
-            .space
-            .elements()
-            .find(|w| w.toplevel().wl_surface() == surface)
-            .cloned()
-        {
+        grabs::resize_grab::handle_commit(&mut self.space, surface);
+
+        if let Some(window) = self.wm.window_for_surface(surface) {
             let initial_configure_sent = with_states(surface, |states| {
                 states
                     .data_map
@@ -42,12 +37,12 @@ impl<B: Backend> CompositorHandler for State<B> {
             });
 
             if !initial_configure_sent {
-                println!("Sending initial configure");
+                trace!("Sending initial configure");
                 let s = self.backend_data.size();
                 let toplevel = window.toplevel();
                 toplevel.with_pending_state(|state| {
-                    state.size = Some(s);
-                    state.decoration_mode = Some(smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode::ServerSide);
+                    // Keep the size the layout already picked
+                    state.size.get_or_insert(s);
                 });
                 toplevel.send_configure();
             }
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                surface::render_elements_from_surface_tree,
+            },
+            ImportAll, ImportMem, Renderer,
+        },
+    },
+    input::pointer::{CursorImageAttributes, CursorImageStatus},
+    output::Output,
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::ResizeEdge,
+    utils::{IsAlive, Logical, Physical, Point, Scale, Transform},
+    wayland::compositor::with_states,
+};
+use xcursor::{parser::parse_xcursor, CursorTheme};
+use tracing::{debug, info};
+
+use crate::drawing_backend::elements::CustomRenderElements;
+
+// Drawn when a client doesn't set a cursor, X is the outline and . the fill.
+// The hotspot is the top left corner.
+const DEFAULT_CURSOR: [&str; 19] = [
+    "X",
+    "XX",
+    "X.X",
+    "X..X",
+    "X...X",
+    "X....X",
+    "X.....X",
+    "X......X",
+    "X.......X",
+    "X........X",
+    "X.........X",
+    "X......XXXXX",
+    "X...X..X",
+    "X..XX..X",
+    "X.X  X..X",
+    "XX   X..X",
+    "X     X..X",
+    "      X..X",
+    "       XX",
+];
+
+fn default_cursor() -> MemoryRenderBuffer {
+    let width = DEFAULT_CURSOR.iter().map(|row| row.len()).max().unwrap();
+    let height = DEFAULT_CURSOR.len();
+
+    // Little endian ARGB8888 is stored as B, G, R, A
+    let mut pixels = vec![0u8; width * height * 4];
+    for (y, row) in DEFAULT_CURSOR.iter().enumerate() {
+        for (x, c) in row.bytes().enumerate() {
+            let pixel = match c {
+                b'X' => [0, 0, 0, 255],
+                b'.' => [255, 255, 255, 255],
+                _ => continue,
+            };
+            let offset = (y * width + x) * 4;
+            pixels[offset..offset + 4].copy_from_slice(&pixel);
+        }
+    }
+
+    MemoryRenderBuffer::from_memory(
+        &pixels,
+        Fourcc::Argb8888,
+        (width as i32, height as i32),
+        1,
+        Transform::Normal,
+        None,
+    )
+}
+
+// Cursor icons are looked up by the first name the theme has, older themes
+// only have the X11 names
+pub type Icon = &'static [&'static str];
+
+pub const DEFAULT_ICON: Icon = &["default", "left_ptr"];
+pub const MOVE_ICON: Icon = &["grabbing", "fleur"];
+
+pub fn resize_icon(edges: ResizeEdge) -> Icon {
+    match edges {
+        ResizeEdge::TopLeft => &["nw-resize", "top_left_corner"],
+        ResizeEdge::TopRight => &["ne-resize", "top_right_corner"],
+        ResizeEdge::BottomLeft => &["sw-resize", "bottom_left_corner"],
+        ResizeEdge::BottomRight => &["se-resize", "bottom_right_corner"],
+        ResizeEdge::Top => &["n-resize", "top_side"],
+        ResizeEdge::Bottom => &["s-resize", "bottom_side"],
+        ResizeEdge::Left => &["w-resize", "left_side"],
+        ResizeEdge::Right => &["e-resize", "right_side"],
+        _ => DEFAULT_ICON,
+    }
+}
+
+struct CursorFrame {
+    buffer: MemoryRenderBuffer,
+    xhot: u32,
+    yhot: u32,
+    // In milliseconds, 0 for cursors that aren't animated
+    delay: u32,
+}
+
+// Loads the frames of the first of `names` in the theme, in the size closest to `size`
+fn load_frames(
+    theme: &CursorTheme,
+    names: Icon,
+    size: u32,
+    scale: u32,
+) -> Option<Vec<CursorFrame>> {
+    let images = names.iter().find_map(|name| {
+        let path = theme.load_icon(name)?;
+        let data = std::fs::read(path).ok()?;
+        parse_xcursor(&data)
+    });
+    let Some(images) = images else {
+        debug!("Cursor {} not found in theme", names[0]);
+        return None;
+    };
+
+    let nearest = images
+        .iter()
+        .min_by_key(|image| (size as i32 - image.size as i32).abs())?
+        .size;
+    let frames = images
+        .into_iter()
+        .filter(|image| image.size == nearest)
+        .map(|image| CursorFrame {
+            // pixels_rgba is stored as R, G, B, A, which is little endian ABGR8888
+            buffer: MemoryRenderBuffer::from_memory(
+                &image.pixels_rgba,
+                Fourcc::Abgr8888,
+                (image.width as i32, image.height as i32),
+                scale as i32,
+                Transform::Normal,
+                None,
+            ),
+            xhot: image.xhot,
+            yhot: image.yhot,
+            delay: image.delay,
+        })
+        .collect::<Vec<_>>();
+    (!frames.is_empty()).then_some(frames)
+}
+
+// The frame of an animated cursor that is shown at `time`
+fn animation_frame(frames: &[CursorFrame], time: Duration) -> Option<&CursorFrame> {
+    let total = frames.iter().map(|frame| frame.delay).sum::<u32>();
+    if total == 0 {
+        return frames.first();
+    }
+    let mut millis = (time.as_millis() % total as u128) as u32;
+    for frame in frames {
+        if millis < frame.delay {
+            return Some(frame);
+        }
+        millis -= frame.delay;
+    }
+    None
+}
+
+// Held by a grab, the cursor shows the icon for as long as the grab lives, so
+// it goes away however the grab ends
+pub struct GrabIcon(Arc<Icon>);
+
+pub struct Cursor {
+    // What the client with pointer focus asked for
+    pub status: CursorImageStatus,
+    // A named cursor the client asked for through cursor-shape-v1
+    shape: Option<Icon>,
+    // Alive while a grab decides the cursor, whatever the client asked for
+    grab_icon: Weak<Icon>,
+    theme: CursorTheme,
+    size: u32,
+    // Loaded icons by name and scale, None when the theme doesn't have them
+    icons: HashMap<(&'static str, u32), Option<Vec<CursorFrame>>>,
+    default: MemoryRenderBuffer,
+}
+
+impl Cursor {
+    // Uses the theme and size from XCURSOR_THEME and XCURSOR_SIZE
+    pub fn new() -> Self {
+        let theme = std::env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = std::env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(24);
+        info!("Using cursor theme {theme} at size {size}");
+
+        Self {
+            status: CursorImageStatus::Default,
+            shape: None,
+            grab_icon: Weak::new(),
+            theme: CursorTheme::load(&theme),
+            size,
+            icons: HashMap::new(),
+            default: default_cursor(),
+        }
+    }
+
+    // A new image replaces the shape, whether it is a surface or a shape
+    pub fn set_status(&mut self, status: CursorImageStatus) {
+        self.status = status;
+        self.shape = None;
+    }
+
+    pub fn set_shape(&mut self, shape: Icon) {
+        self.status = CursorImageStatus::Default;
+        self.shape = Some(shape);
+    }
+
+    pub fn grab_icon(&mut self, icon: Icon) -> GrabIcon {
+        let icon = Arc::new(icon);
+        self.grab_icon = Arc::downgrade(&icon);
+        GrabIcon(icon)
+    }
+
+    fn load(&mut self, icon: Icon, scale: u32) {
+        let size = self.size * scale;
+        self.icons
+            .entry((icon[0], scale))
+            .or_insert_with(|| load_frames(&self.theme, icon, size, scale));
+    }
+
+    // The cursor at `location`, drawn above everything else on the output
+    pub fn render_elements<R>(
+        &mut self,
+        renderer: &mut R,
+        location: Point<f64, Logical>,
+        output: &Output,
+        time: Duration,
+    ) -> Vec<CustomRenderElements<R>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        <R as Renderer>::TextureId: 'static,
+    {
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let location = location - output.current_location().to_f64();
+
+        let icon = if let Some(icon) = self.grab_icon.upgrade() {
+            *icon
+        } else {
+            match &self.status {
+                CursorImageStatus::Hidden => return vec![],
+                CursorImageStatus::Surface(surface) if surface.alive() => {
+                    let hotspot = with_states(surface, |states| {
+                        states
+                            .data_map
+                            .get::<Mutex<CursorImageAttributes>>()
+                            .unwrap()
+                            .lock()
+                            .unwrap()
+                            .hotspot
+                    });
+                    let location: Point<i32, Physical> =
+                        (location - hotspot.to_f64()).to_physical_precise_round(scale);
+                    return render_elements_from_surface_tree(renderer, surface, location, scale);
+                }
+                // Also used once the client's cursor surface is gone
+                _ => self.shape.unwrap_or(DEFAULT_ICON),
+            }
+        };
+
+        let integer_scale = output.current_scale().integer_scale().max(1) as u32;
+        self.load(icon, integer_scale);
+        let frame = self.icons[&(icon[0], integer_scale)]
+            .as_deref()
+            .and_then(|frames| animation_frame(frames, time));
+        let (buffer, location) = match frame {
+            Some(frame) => {
+                let hotspot = Point::<f64, Logical>::from((
+                    frame.xhot as f64 / integer_scale as f64,
+                    frame.yhot as f64 / integer_scale as f64,
+                ));
+                (&frame.buffer, location - hotspot)
+            }
+            // Without a theme there is only the built-in cursor
+            None => (&self.default, location),
+        };
+
+        MemoryRenderBufferRenderElement::from_buffer(
+            renderer,
+            location.to_physical(scale),
+            buffer,
+            None,
+            None,
+            None,
+        )
+        .map(|element| vec![element.into()])
+        .unwrap_or_default()
+    }
+}
@@ -0,0 +1,184 @@
+use smithay::{
+    reexports::wayland_server::{
+        backend::GlobalId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+        Resource, WEnum,
+    },
+    utils::Serial,
+};
+
+use crate::{
+    cursor::{self, Icon},
+    drawing_backend::Backend,
+    state::State,
+};
+
+use self::protocol::{
+    wp_cursor_shape_device_v1::{self, Shape, WpCursorShapeDeviceV1},
+    wp_cursor_shape_manager_v1::{self, WpCursorShapeManagerV1},
+};
+
+// cursor-shape-v1 is not in wayland-protocols yet, so it is generated here
+// the same way wayland-protocols does it
+#[allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#[allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#[allow(missing_docs, clippy::all)]
+pub mod protocol {
+    use smithay::reexports::wayland_protocols::wp::tablet::zv2::server::*;
+    use smithay::reexports::wayland_server;
+    use smithay::reexports::wayland_server::protocol::*;
+
+    pub mod __interfaces {
+        use smithay::reexports::wayland_protocols::wp::tablet::zv2::server::__interfaces::*;
+        use smithay::reexports::wayland_server::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/cursor-shape-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_server_code!("protocols/cursor-shape-v1.xml");
+}
+
+pub struct CursorShapeManagerState {
+    global: GlobalId,
+}
+
+impl CursorShapeManagerState {
+    pub fn new<D>(dh: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<WpCursorShapeManagerV1, ()> + 'static,
+    {
+        let global = dh.create_global::<D, WpCursorShapeManagerV1, _>(1, ());
+        Self { global }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+// Tablets are not supported, their devices are accepted but do nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShapeDevice {
+    Pointer,
+    TabletTool,
+}
+
+// The cursor theme names for a shape, with the X11 names as fallback
+pub fn shape_icon(shape: Shape) -> Icon {
+    match shape {
+        Shape::Default => cursor::DEFAULT_ICON,
+        Shape::ContextMenu => &["context-menu", "left_ptr"],
+        Shape::Help => &["help", "question_arrow"],
+        Shape::Pointer => &["pointer", "hand2"],
+        Shape::Progress => &["progress", "left_ptr_watch"],
+        Shape::Wait => &["wait", "watch"],
+        Shape::Cell => &["cell", "plus"],
+        Shape::Crosshair => &["crosshair", "cross"],
+        Shape::Text => &["text", "xterm"],
+        Shape::VerticalText => &["vertical-text", "xterm"],
+        Shape::Alias => &["alias", "dnd-link"],
+        Shape::Copy => &["copy", "dnd-copy"],
+        Shape::Move => &["move", "fleur"],
+        Shape::NoDrop => &["no-drop", "dnd-none"],
+        Shape::NotAllowed => &["not-allowed", "crossed_circle"],
+        Shape::Grab => &["grab", "hand1"],
+        Shape::Grabbing => cursor::MOVE_ICON,
+        Shape::EResize => &["e-resize", "right_side"],
+        Shape::NResize => &["n-resize", "top_side"],
+        Shape::NeResize => &["ne-resize", "top_right_corner"],
+        Shape::NwResize => &["nw-resize", "top_left_corner"],
+        Shape::SResize => &["s-resize", "bottom_side"],
+        Shape::SeResize => &["se-resize", "bottom_right_corner"],
+        Shape::SwResize => &["sw-resize", "bottom_left_corner"],
+        Shape::WResize => &["w-resize", "left_side"],
+        Shape::EwResize => &["ew-resize", "sb_h_double_arrow"],
+        Shape::NsResize => &["ns-resize", "sb_v_double_arrow"],
+        Shape::NeswResize => &["nesw-resize", "fd_double_arrow"],
+        Shape::NwseResize => &["nwse-resize", "bd_double_arrow"],
+        Shape::ColResize => &["col-resize", "sb_h_double_arrow"],
+        Shape::RowResize => &["row-resize", "sb_v_double_arrow"],
+        Shape::AllScroll => &["all-scroll", "fleur"],
+        Shape::ZoomIn => &["zoom-in"],
+        Shape::ZoomOut => &["zoom-out"],
+        _ => cursor::DEFAULT_ICON,
+    }
+}
+
+impl<B: Backend> GlobalDispatch<WpCursorShapeManagerV1, ()> for State<B> {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WpCursorShapeManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<B: Backend> Dispatch<WpCursorShapeManagerV1, ()> for State<B> {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WpCursorShapeManagerV1,
+        request: wp_cursor_shape_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wp_cursor_shape_manager_v1::Request::GetPointer {
+                cursor_shape_device,
+                ..
+            } => {
+                data_init.init(cursor_shape_device, CursorShapeDevice::Pointer);
+            }
+            wp_cursor_shape_manager_v1::Request::GetTabletToolV2 {
+                cursor_shape_device,
+                ..
+            } => {
+                data_init.init(cursor_shape_device, CursorShapeDevice::TabletTool);
+            }
+            wp_cursor_shape_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<B: Backend> Dispatch<WpCursorShapeDeviceV1, CursorShapeDevice> for State<B> {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &WpCursorShapeDeviceV1,
+        request: wp_cursor_shape_device_v1::Request,
+        data: &CursorShapeDevice,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wp_cursor_shape_device_v1::Request::SetShape { serial, shape } => {
+                let WEnum::Value(shape) = shape else {
+                    resource.post_error(
+                        wp_cursor_shape_device_v1::Error::InvalidShape,
+                        "unknown cursor shape",
+                    );
+                    return;
+                };
+                if *data != CursorShapeDevice::Pointer {
+                    return;
+                }
+
+                // Only the client with pointer focus gets to pick the cursor, and
+                // only in reply to its latest enter
+                let Some((surface, enter)) = &state.pointer_enter else {
+                    return;
+                };
+                if surface.id().same_client_as(&resource.id()) && Serial::from(serial) == *enter {
+                    state.cursor.set_shape(shape_icon(shape));
+                }
+            }
+            wp_cursor_shape_device_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
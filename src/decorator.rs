@@ -1,19 +1,420 @@
 use smithay::{wayland::shell::{xdg::{decoration::XdgDecorationHandler, ToplevelSurface}, kde::decoration::{KdeDecorationHandler, KdeDecorationState}}, delegate_xdg_decoration, reexports::{wayland_server::{protocol::wl_surface::WlSurface, WEnum}, wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::{Mode as XdgMode}, wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration::{OrgKdeKwinServerDecoration, Mode as KdeMode}}, delegate_kde_decoration};
 
-use crate::{state::State, drawing_backend::Backend};
+use smithay::{desktop::Window, input::pointer::GrabStartData as PointerGrabStartData, reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::ResizeEdge, utils::{Logical, Point, Rectangle, Serial}, wayland::{compositor, shell::xdg::XdgToplevelSurfaceData}};
 
+use std::{cell::RefCell, collections::HashMap};
+use tracing::{debug, trace, warn};
 
+use crate::{state::State, drawing_backend::Backend, grabs::BTN_LEFT, window_manager::send_configure_if_ready};
 
+// How server side decorations look. The frame is drawn around the client's
+// window geometry, with the title bar between the top border and the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationStyle {
+    pub border_width: i32,
+    // A height of 0 disables title bars
+    pub title_height: i32,
+    pub focused_color: [f32; 4],
+    pub unfocused_color: [f32; 4],
+    pub title_text_color: [f32; 4],
+    pub close_color: [f32; 4],
+    pub maximize_color: [f32; 4],
+    pub minimize_color: [f32; 4],
+}
 
+impl Default for DecorationStyle {
+    fn default() -> Self {
+        Self {
+            border_width: 2,
+            title_height: 20,
+            focused_color: [0.36, 0.55, 0.85, 1.0],
+            unfocused_color: [0.25, 0.25, 0.28, 1.0],
+            title_text_color: [1.0, 1.0, 1.0, 1.0],
+            close_color: [0.85, 0.3, 0.3, 1.0],
+            maximize_color: [0.35, 0.75, 0.4, 1.0],
+            minimize_color: [0.9, 0.75, 0.3, 1.0],
+        }
+    }
+}
 
-impl<B: Backend> XdgDecorationHandler for State<B> {
-    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
-        println!("New XDG decoration");
+impl DecorationStyle {
+    pub fn title_bar(&self, client: Rectangle<i32, Logical>) -> Option<Rectangle<i32, Logical>> {
+        (self.title_height > 0).then(|| {
+            Rectangle::from_loc_and_size(
+                (client.loc.x, client.loc.y - self.title_height),
+                (client.size.w, self.title_height),
+            )
+        })
+    }
+
+    // Buttons from the right end of the title bar, as many as fit
+    pub fn buttons(
+        &self,
+        client: Rectangle<i32, Logical>,
+    ) -> Vec<(TitleButton, Rectangle<i32, Logical>)> {
+        let Some(title_bar) = self.title_bar(client) else {
+            return vec![];
+        };
+        let margin = self.title_height / 5;
+        let size = self.title_height - 2 * margin;
+        TitleButton::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(i, button)| {
+                let x = title_bar.loc.x + title_bar.size.w - (i as i32 + 1) * (size + margin);
+                let rect =
+                    Rectangle::from_loc_and_size((x, title_bar.loc.y + margin), (size, size));
+                (button, rect)
+            })
+            .take_while(|(_, rect)| rect.loc.x >= title_bar.loc.x)
+            .collect()
+    }
+
+    pub fn button_color(&self, button: TitleButton) -> [f32; 4] {
+        match button {
+            TitleButton::Close => self.close_color,
+            TitleButton::Maximize => self.maximize_color,
+            TitleButton::Minimize => self.minimize_color,
+        }
+    }
+
+    // What part of the frame around `client` is at `point`, if any
+    pub fn hit_test(
+        &self,
+        client: Rectangle<i32, Logical>,
+        point: Point<f64, Logical>,
+    ) -> Option<DecorationHit> {
+        let frame = self.frame_geometry(client);
+        if !frame.to_f64().contains(point) || client.to_f64().contains(point) {
+            return None;
+        }
+
+        if let Some((button, _)) = self
+            .buttons(client)
+            .into_iter()
+            .find(|(_, rect)| rect.to_f64().contains(point))
+        {
+            return Some(DecorationHit::Button(button));
+        }
+        if let Some(title_bar) = self.title_bar(client)
+            && title_bar.to_f64().contains(point)
+        {
+            return Some(DecorationHit::TitleBar);
+        }
+
+        let left = point.x < client.loc.x as f64;
+        let right = point.x >= (client.loc.x + client.size.w) as f64;
+        let top = point.y < (frame.loc.y + self.border_width) as f64;
+        let bottom = point.y >= (client.loc.y + client.size.h) as f64;
+        let edge = match (top, bottom, left, right) {
+            (true, _, true, _) => ResizeEdge::TopLeft,
+            (true, _, _, true) => ResizeEdge::TopRight,
+            (_, true, true, _) => ResizeEdge::BottomLeft,
+            (_, true, _, true) => ResizeEdge::BottomRight,
+            (true, ..) => ResizeEdge::Top,
+            (_, true, ..) => ResizeEdge::Bottom,
+            (_, _, true, _) => ResizeEdge::Left,
+            _ => ResizeEdge::Right,
+        };
+        Some(DecorationHit::Border(edge))
+    }
+
+    // The top, bottom, left and right borders around a client
+    pub fn borders(&self, client: Rectangle<i32, Logical>) -> [Rectangle<i32, Logical>; 4] {
+        let frame = self.frame_geometry(client);
+        let b = self.border_width;
+        let inner_h = frame.size.h - 2 * b;
+        [
+            Rectangle::from_loc_and_size(frame.loc, (frame.size.w, b)),
+            Rectangle::from_loc_and_size(
+                (frame.loc.x, frame.loc.y + frame.size.h - b),
+                (frame.size.w, b),
+            ),
+            Rectangle::from_loc_and_size((frame.loc.x, frame.loc.y + b), (b, inner_h)),
+            Rectangle::from_loc_and_size(
+                (frame.loc.x + frame.size.w - b, frame.loc.y + b),
+                (b, inner_h),
+            ),
+        ]
+    }
+
+    pub fn frame_geometry(&self, client: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let b = self.border_width;
+        let t = self.title_height;
+        Rectangle::from_loc_and_size(
+            (client.loc.x - b, client.loc.y - b - t),
+            (client.size.w + 2 * b, client.size.h + 2 * b + t),
+        )
+    }
+
+    pub fn client_geometry(&self, frame: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let b = self.border_width;
+        let t = self.title_height;
+        Rectangle::from_loc_and_size(
+            (frame.loc.x + b, frame.loc.y + b + t),
+            (
+                (frame.size.w - 2 * b).max(1),
+                (frame.size.h - 2 * b - t).max(1),
+            ),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleButton {
+    Close,
+    Maximize,
+    Minimize,
+}
+
+impl TitleButton {
+    // In order from the right
+    pub const ALL: [TitleButton; 3] = [
+        TitleButton::Close,
+        TitleButton::Maximize,
+        TitleButton::Minimize,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationHit {
+    TitleBar,
+    Button(TitleButton),
+    Border(ResizeEdge),
+}
+
+// Decides who draws a window's frame, from the app id and what the client asked
+// for. Rules for specific app ids take precedence over the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationPolicy {
+    // Grant whatever the client asks for, server side if it doesn't care
+    FollowClient,
+    AlwaysServer,
+    AlwaysClient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecorationRules {
+    pub default: DecorationPolicy,
+    pub app_rules: HashMap<String, DecorationPolicy>,
+}
+
+impl Default for DecorationRules {
+    fn default() -> Self {
+        Self {
+            default: DecorationPolicy::FollowClient,
+            app_rules: HashMap::new(),
+        }
+    }
+}
+
+impl DecorationPolicy {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "follow" => Some(DecorationPolicy::FollowClient),
+            "server" => Some(DecorationPolicy::AlwaysServer),
+            "client" => Some(DecorationPolicy::AlwaysClient),
+            _ => None,
+        }
+    }
+}
+
+impl DecorationRules {
+    // NEKOWM_DECORATIONS is a comma separated list of policies, "follow", "server"
+    // or "client", either alone for the default or as "app_id=policy"
+    pub fn from_env() -> Self {
+        let mut rules = Self::default();
+        let Ok(var) = std::env::var("NEKOWM_DECORATIONS") else {
+            return rules;
+        };
+        for rule in var.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let (app_id, name) = match rule.split_once('=') {
+                Some((app_id, name)) => (Some(app_id.trim()), name.trim()),
+                None => (None, rule),
+            };
+            let Some(policy) = DecorationPolicy::from_name(name) else {
+                warn!("Invalid decoration rule {rule:?}");
+                continue;
+            };
+            match app_id {
+                Some(app_id) => {
+                    rules.app_rules.insert(app_id.to_owned(), policy);
+                }
+                None => rules.default = policy,
+            }
+        }
+        rules
+    }
+
+    pub fn policy(&self, app_id: Option<&str>) -> DecorationPolicy {
+        app_id
+            .and_then(|id| self.app_rules.get(id))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    pub fn decide(&self, app_id: Option<&str>, requested: Option<XdgMode>) -> XdgMode {
+        match self.policy(app_id) {
+            DecorationPolicy::AlwaysServer => XdgMode::ServerSide,
+            DecorationPolicy::AlwaysClient => XdgMode::ClientSide,
+            DecorationPolicy::FollowClient => requested.unwrap_or(XdgMode::ServerSide),
+        }
+    }
+}
+
+// Who draws the frame of a toplevel, kept in its surface data. Toplevels that
+// never negotiate a mode decorate themselves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationState {
+    #[default]
+    Client,
+    Server,
+}
+
+impl DecorationState {
+    fn with<F, T>(surface: &WlSurface, cb: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        compositor::with_states(surface, |states| {
+            states.data_map.insert_if_missing(RefCell::<Self>::default);
+            let state = states.data_map.get::<RefCell<Self>>().unwrap();
+
+            cb(&mut state.borrow_mut())
+        })
+    }
+
+    pub fn get(surface: &WlSurface) -> Self {
+        Self::with(surface, |state| *state)
+    }
+
+    pub fn is_server(surface: &WlSurface) -> bool {
+        Self::get(surface) == DecorationState::Server
+    }
+}
+
+impl From<XdgMode> for DecorationState {
+    fn from(mode: XdgMode) -> Self {
+        match mode {
+            XdgMode::ServerSide => DecorationState::Server,
+            _ => DecorationState::Client,
+        }
+    }
+}
+
+fn app_id(surface: &WlSurface) -> Option<String> {
+    compositor::with_states(surface, |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()?
+            .lock()
+            .unwrap()
+            .app_id
+            .clone()
+    })
+}
+
+impl<B: Backend> State<B> {
+    fn decide_decoration(&self, surface: &WlSurface, requested: Option<XdgMode>) -> XdgMode {
+        let mode = self
+            .wm
+            .decoration_rules()
+            .decide(app_id(surface).as_deref(), requested);
+        debug!("Decoration mode {mode:?} (requested {requested:?})");
+        mode
+    }
+
+    // The frame changes the size left for the client, so tiled windows are laid out again
+    fn set_decoration_state(&mut self, surface: &WlSurface, state: DecorationState) {
+        let changed = DecorationState::with(surface, |current| {
+            std::mem::replace(current, state) != state
+        });
+        if changed {
+            self.wm.layout(&mut self.space);
+        }
+    }
+
+    fn configure_decoration(&mut self, toplevel: &ToplevelSurface, requested: Option<XdgMode>) {
+        let mode = self.decide_decoration(toplevel.wl_surface(), requested);
         toplevel.with_pending_state(|state| {
-            // Advertise server side decoration
-            state.decoration_mode = Some(XdgMode::ServerSide);
+            state.decoration_mode = Some(mode);
         });
-        toplevel.send_configure();
+        self.set_decoration_state(toplevel.wl_surface(), mode.into());
+        send_configure_if_ready(toplevel);
+    }
+}
+
+impl<B: Backend> State<B> {
+    // The frame at `point`, unless some window above it is there
+    pub fn decoration_under(&self, point: Point<f64, Logical>) -> Option<(Window, DecorationHit)> {
+        let style = self.wm.decoration_style();
+        let windows = self.space.elements().collect::<Vec<_>>();
+        for window in windows.into_iter().rev() {
+            let Some(loc) = self.space.element_location(window) else {
+                continue;
+            };
+            let client = Rectangle::from_loc_and_size(loc, window.geometry().size);
+            let decorated = DecorationState::is_server(window.toplevel().wl_surface());
+            let frame = if decorated {
+                style.frame_geometry(client)
+            } else {
+                client
+            };
+            if !frame.to_f64().contains(point) {
+                continue;
+            }
+
+            return style
+                .hit_test(client, point)
+                .filter(|_| decorated)
+                .map(|hit| (window.clone(), hit));
+        }
+        None
+    }
+
+    pub fn decoration_clicked(
+        &mut self,
+        window: Window,
+        hit: DecorationHit,
+        button: u32,
+        serial: Serial,
+    ) {
+        if button != BTN_LEFT {
+            return;
+        }
+        self.set_focus(Some(window.clone()));
+
+        // Only floating windows can be resized by hand, tiled ones float when dragged
+        let maximized = self.wm.is_maximized(&window);
+        let floating = self.wm.is_floating(&window);
+        let start_data = PointerGrabStartData {
+            focus: None,
+            button,
+            location: self.pointer_location,
+        };
+        match hit {
+            DecorationHit::Button(TitleButton::Close) => window.toplevel().send_close(),
+            DecorationHit::Button(TitleButton::Maximize) => {
+                self.wm.toggle_maximized(&window, &mut self.space)
+            }
+            DecorationHit::Button(TitleButton::Minimize) => {
+                self.wm.minimize(&window, &mut self.space);
+                let focus = self.wm.focus_candidate();
+                self.set_focus(focus);
+            }
+            DecorationHit::TitleBar if !maximized => self.start_move(window, start_data, serial),
+            DecorationHit::Border(edges) if floating && !maximized => {
+                self.start_resize(window, edges, start_data, serial)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<B: Backend> XdgDecorationHandler for State<B> {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        trace!("New XDG decoration");
+        self.configure_decoration(&toplevel, None);
     }
 
     fn request_mode(
@@ -21,16 +422,13 @@ impl<B: Backend> XdgDecorationHandler for State<B> {
         toplevel: ToplevelSurface,
         mode: XdgMode,
     ) {
-        let s = self.backend_data.size();
-        println!("Mode {mode:?} requested");
-        toplevel.with_pending_state(|state| {
-            state.size = Some(s);
-        });
-        toplevel.send_configure();
+        trace!("Mode {mode:?} requested");
+        self.configure_decoration(&toplevel, Some(mode));
     }
 
     fn unset_mode(&mut self, toplevel: ToplevelSurface) {
-        println!("Mode unset");
+        trace!("Mode unset");
+        self.configure_decoration(&toplevel, None);
     }
 }
 
@@ -41,21 +439,39 @@ impl<B: Backend> KdeDecorationHandler for State<B> {
         &self.kde_decoration_state
     }
 
-    fn new_decoration(&mut self, _surface: &WlSurface, _decoration: &OrgKdeKwinServerDecoration) {
-        println!("Decoration created");
+    fn new_decoration(&mut self, surface: &WlSurface, decoration: &OrgKdeKwinServerDecoration) {
+        trace!("Decoration created");
+        let mode = self.decide_decoration(surface, None);
+        decoration.mode(kde_mode(mode));
+        self.set_decoration_state(surface, mode.into());
     }
 
     fn request_mode(
         &mut self,
-        _surface: &WlSurface,
+        surface: &WlSurface,
         decoration: &OrgKdeKwinServerDecoration,
         mode: WEnum<KdeMode>,
     ) {
-        println!("Mode: {mode:?}");
+        trace!("Mode: {mode:?}");
+        let requested = match mode {
+            WEnum::Value(KdeMode::Server) => XdgMode::ServerSide,
+            _ => XdgMode::ClientSide,
+        };
+        let mode = self.decide_decoration(surface, Some(requested));
+        decoration.mode(kde_mode(mode));
+        self.set_decoration_state(surface, mode.into());
     }
 
-    fn release(&mut self, _decoration: &OrgKdeKwinServerDecoration, _surface: &WlSurface) {
-        println!("Decoration released");
+    fn release(&mut self, _decoration: &OrgKdeKwinServerDecoration, surface: &WlSurface) {
+        trace!("Decoration released");
+        self.set_decoration_state(surface, DecorationState::Client);
+    }
+}
+
+fn kde_mode(mode: XdgMode) -> KdeMode {
+    match mode {
+        XdgMode::ServerSide => KdeMode::Server,
+        _ => KdeMode::Client,
     }
 }
 
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                surface::WaylandSurfaceRenderElement,
+                AsRenderElements,
+            },
+            ImportAll, ImportMem, Renderer,
+        },
+    },
+    desktop::{Space, Window},
+    output::Output,
+    render_elements,
+    utils::{Logical, Point, Rectangle, Scale, Transform},
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
+};
+
+use crate::decorator::{DecorationState, DecorationStyle};
+
+use super::font;
+
+render_elements! {
+    pub CustomRenderElements<R> where R: ImportAll + ImportMem;
+    Surface=WaylandSurfaceRenderElement<R>,
+    Solid=SolidColorRenderElement,
+    Memory=MemoryRenderBufferRenderElement<R>,
+}
+
+const TITLE_PADDING: i32 = 6;
+
+struct TitleText {
+    // What the buffer was rendered from
+    text: String,
+    max_width: i32,
+    color: [u8; 4],
+    buffer: MemoryRenderBuffer,
+}
+
+// The buffers have to outlive a frame for damage tracking to work
+struct Frame {
+    // The title bar buttons, the four borders and the title bar
+    parts: [SolidColorBuffer; 8],
+    title: Option<TitleText>,
+}
+
+impl Frame {
+    fn new() -> Self {
+        Self {
+            parts: std::array::from_fn(|_| SolidColorBuffer::new((0, 0), [0.0; 4])),
+            title: None,
+        }
+    }
+
+    fn render_elements<R>(
+        &mut self,
+        renderer: &mut R,
+        window: &Window,
+        client: Rectangle<i32, Logical>,
+        focused: bool,
+        style: &DecorationStyle,
+        scale: Scale<f64>,
+    ) -> Vec<CustomRenderElements<R>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        <R as Renderer>::TextureId: 'static,
+    {
+        let mut elements = Vec::new();
+        let color = if focused {
+            style.focused_color
+        } else {
+            style.unfocused_color
+        };
+        let title_bar = style.title_bar(client);
+        let buttons = style.buttons(client);
+
+        // Front to back, so the buttons come first
+        let mut parts = buttons
+            .iter()
+            .map(|(button, rect)| (*rect, style.button_color(*button)))
+            .collect::<Vec<_>>();
+        let text_index = parts.len();
+        parts.extend(
+            style
+                .borders(client)
+                .into_iter()
+                .chain(title_bar)
+                .map(|rect| (rect, color)),
+        );
+
+        for (buffer, (rect, color)) in self.parts.iter_mut().zip(parts) {
+            if rect.is_empty() {
+                continue;
+            }
+            buffer.update(rect.size, color);
+            elements.push(
+                SolidColorRenderElement::from_buffer(
+                    buffer,
+                    rect.loc.to_physical_precise_round(scale),
+                    scale,
+                )
+                .into(),
+            );
+        }
+
+        if let Some(title_bar) = title_bar {
+            let text = window_title(window);
+            // Up to the leftmost button
+            let text_end = buttons
+                .last()
+                .map_or(title_bar.loc.x + title_bar.size.w, |(_, rect)| rect.loc.x);
+            let max_width = text_end - title_bar.loc.x - 2 * TITLE_PADDING;
+            let text_color = to_rgba8(style.title_text_color);
+            let glyph_scale = ((style.title_height - 4) / font::GLYPH_HEIGHT).max(1);
+
+            let stale = self.title.as_ref().map_or(true, |t| {
+                t.text != text || t.max_width != max_width || t.color != text_color
+            });
+            if stale {
+                let (pixels, size) = font::rasterize(&text, glyph_scale, text_color, max_width);
+                self.title = Some(TitleText {
+                    text,
+                    max_width,
+                    color: text_color,
+                    buffer: MemoryRenderBuffer::from_memory(
+                        &pixels,
+                        Fourcc::Argb8888,
+                        size,
+                        1,
+                        Transform::Normal,
+                        None,
+                    ),
+                });
+            }
+
+            if let Some(title) = &self.title && !title.text.is_empty() {
+                let text_height = font::GLYPH_HEIGHT * glyph_scale;
+                let loc = (
+                    title_bar.loc.x + TITLE_PADDING,
+                    title_bar.loc.y + (title_bar.size.h - text_height) / 2,
+                );
+                let loc = Point::<i32, Logical>::from(loc).to_physical_precise_round(scale);
+                if let Ok(element) = MemoryRenderBufferRenderElement::from_buffer(
+                    renderer,
+                    loc.to_f64(),
+                    &title.buffer,
+                    None,
+                    None,
+                    None,
+                ) {
+                    let index = text_index.min(elements.len());
+                    elements.insert(index, element.into());
+                }
+            }
+        }
+
+        elements
+    }
+}
+
+#[derive(Default)]
+pub struct Decorations {
+    frames: HashMap<Window, Frame>,
+}
+
+fn window_title(window: &Window) -> String {
+    with_states(window.toplevel().wl_surface(), |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .title
+            .clone()
+            .unwrap_or_default()
+    })
+}
+
+fn to_rgba8(color: [f32; 4]) -> [u8; 4] {
+    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+// Everything to draw on an output, front to back. Windows are drawn together
+// with their frames so a frame never ends up above a window stacked on top of it.
+pub fn output_elements<R>(
+    renderer: &mut R,
+    space: &Space<Window>,
+    decorations: &mut Decorations,
+    style: &DecorationStyle,
+    focused: Option<&Window>,
+    output: &Output,
+) -> Vec<CustomRenderElements<R>>
+where
+    R: Renderer + ImportAll + ImportMem,
+    <R as Renderer>::TextureId: 'static,
+{
+    let output_loc = space
+        .output_geometry(output)
+        .map(|geometry| geometry.loc)
+        .unwrap_or_default();
+    let scale = Scale::from(output.current_scale().fractional_scale());
+
+    let windows = space.elements().collect::<Vec<_>>();
+    decorations.frames.retain(|w, _| {
+        windows.contains(&w) && DecorationState::is_server(w.toplevel().wl_surface())
+    });
+
+    let mut elements = Vec::new();
+    for window in windows.into_iter().rev() {
+        let Some(loc) = space.element_location(window) else {
+            continue;
+        };
+
+        let render_loc =
+            (loc - window.geometry().loc - output_loc).to_physical_precise_round(scale);
+        elements.extend(AsRenderElements::<R>::render_elements::<
+            CustomRenderElements<R>,
+        >(window, renderer, render_loc, scale));
+
+        if !DecorationState::is_server(window.toplevel().wl_surface()) {
+            continue;
+        }
+        let client = Rectangle::from_loc_and_size(loc - output_loc, window.geometry().size);
+        let frame = decorations
+            .frames
+            .entry(window.clone())
+            .or_insert_with(Frame::new);
+        elements.extend(frame.render_elements(
+            renderer,
+            window,
+            client,
+            focused == Some(window),
+            style,
+            scale,
+        ));
+    }
+
+    elements
+}
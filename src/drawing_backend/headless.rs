@@ -0,0 +1,244 @@
+use std::{fs::OpenOptions, os::fd::OwnedFd, path::PathBuf, time::Duration};
+
+use smithay::{
+    backend::{
+        allocator::gbm::GbmDevice,
+        egl::{EGLContext, EGLDevice, EGLDisplay, EGLNativeDisplay},
+        renderer::{
+            damage::DamageTrackedRenderer,
+            gles2::{Gles2Renderbuffer, Gles2Renderer},
+            Bind, Offscreen,
+        },
+    },
+    desktop::{utils::send_frames_surface_tree, Space, Window},
+    input::pointer::CursorImageStatus,
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{calloop::EventLoop, wayland_server::Display},
+    utils::{DeviceFd, Logical, Size},
+};
+use tracing::{error, info, warn};
+
+use crate::{state::State, CalloopData};
+
+use super::{elements, Backend};
+
+fn default_mode() -> Mode {
+    Mode {
+        size: (1920, 1080).into(),
+        refresh: 60_000,
+    }
+}
+
+pub struct HeadlessBackendData {
+    renderer: Gles2Renderer,
+    buffer: Gles2Renderbuffer,
+    damage_tracked_renderer: DamageTrackedRenderer,
+    output: Output,
+    mode: Mode,
+}
+
+/// Parses a mode of the form `WIDTHxHEIGHT` or `WIDTHxHEIGHT@HZ`.
+pub fn parse_mode(s: &str) -> Option<Mode> {
+    let (size, refresh) = match s.split_once('@') {
+        Some((size, refresh)) => (size, refresh.parse::<f64>().ok()?),
+        None => (s, 60.0),
+    };
+    let (w, h) = size.split_once('x')?;
+    let (w, h) = (w.parse::<i32>().ok()?, h.parse::<i32>().ok()?);
+    if w <= 0 || h <= 0 || refresh <= 0.0 {
+        return None;
+    }
+
+    Some(Mode {
+        size: (w, h).into(),
+        refresh: (refresh * 1000.0) as i32,
+    })
+}
+
+fn find_render_node() -> Option<PathBuf> {
+    let mut nodes = std::fs::read_dir("/dev/dri")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with("renderD"))
+        })
+        .collect::<Vec<_>>();
+    nodes.sort();
+    nodes.into_iter().next()
+}
+
+fn create_renderer(
+    native: impl EGLNativeDisplay + 'static,
+    mode: Mode,
+) -> Option<(Gles2Renderer, Gles2Renderbuffer)> {
+    let egl = EGLDisplay::new(native).ok()?;
+    let context = EGLContext::new(&egl).ok()?;
+    let mut renderer = unsafe { Gles2Renderer::new(context) }.ok()?;
+    let buffer: Gles2Renderbuffer = renderer
+        .create_buffer((mode.size.w, mode.size.h).into())
+        .ok()?;
+    Some((renderer, buffer))
+}
+
+fn init_gpu_renderer(mode: Mode) -> Option<(Gles2Renderer, Gles2Renderbuffer)> {
+    let path = find_render_node()?;
+    let file = match OpenOptions::new().read(true).write(true).open(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Could not open {}: {err}", path.display());
+            return None;
+        }
+    };
+    let device = GbmDevice::new(DeviceFd::from(OwnedFd::from(file))).ok()?;
+    let renderer = create_renderer(device, mode)?;
+    info!("Headless rendering on {}", path.display());
+    Some(renderer)
+}
+
+// Without a render node, Mesa still offers llvmpipe as an EGL device with no
+// DRM node behind it, which renders on the CPU
+fn init_software_renderer(mode: Mode) -> Option<(Gles2Renderer, Gles2Renderbuffer)> {
+    let devices = match EGLDevice::enumerate() {
+        Ok(devices) => devices,
+        Err(err) => {
+            warn!("Could not enumerate EGL devices: {err}");
+            return None;
+        }
+    };
+    for device in devices {
+        if !matches!(device.try_get_render_node(), Ok(None)) {
+            continue;
+        }
+        if let Some(renderer) = create_renderer(device, mode) {
+            info!("Headless rendering in software");
+            return Some(renderer);
+        }
+    }
+    None
+}
+
+fn init_renderer(mode: Mode) -> (Gles2Renderer, Gles2Renderbuffer) {
+    init_gpu_renderer(mode)
+        .or_else(|| init_software_renderer(mode))
+        .unwrap_or_else(|| {
+            error!("No render node and no software EGL device (is Mesa's llvmpipe installed?)");
+            std::process::exit(1);
+        })
+}
+
+fn headless_draw(data: &mut CalloopData<HeadlessBackendData>) {
+    let focused = data.state.focused_window();
+    let backend_data = &mut data.state.backend_data;
+    let renderer = &mut backend_data.renderer;
+    let damage_tracked_renderer = &mut backend_data.damage_tracked_renderer;
+    let output = &backend_data.output;
+
+    renderer.bind(backend_data.buffer.clone()).unwrap();
+    let mut elements = data.state.cursor.render_elements(
+        renderer,
+        data.state.pointer_location,
+        output,
+        data.state.start_time.elapsed(),
+    );
+    elements.extend(elements::output_elements(
+        renderer,
+        &data.state.space,
+        &mut data.state.decorations,
+        data.state.wm.decoration_style(),
+        focused.as_ref(),
+        output,
+    ));
+    damage_tracked_renderer
+        .render_output(renderer, 0, &elements, [0.5, 0.5, 0.5, 1.0])
+        .unwrap();
+
+    data.state.space.elements().for_each(|window| {
+        window.send_frame(
+            output,
+            data.state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        )
+    });
+
+    if let CursorImageStatus::Surface(surface) = &data.state.cursor.status {
+        send_frames_surface_tree(
+            surface,
+            output,
+            data.state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        );
+    }
+
+    data.state.space.refresh();
+    data.display.flush_clients().unwrap();
+}
+
+pub fn init_headless(
+    _event_loop: &mut EventLoop<CalloopData<HeadlessBackendData>>,
+    display: &mut Display<State<HeadlessBackendData>>,
+    space: &mut Space<Window>,
+) -> HeadlessBackendData {
+    let mode = match std::env::var("NEKOWM_HEADLESS_MODE") {
+        Ok(s) => parse_mode(&s).unwrap_or_else(|| {
+            warn!("Invalid NEKOWM_HEADLESS_MODE {s:?}, using default");
+            default_mode()
+        }),
+        Err(_) => default_mode(),
+    };
+
+    let (renderer, buffer) = init_renderer(mode);
+
+    let output = Output::new(
+        "headless".into(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "NekoWM".into(),
+            model: "headless".into(),
+        },
+    );
+
+    output.create_global::<State<HeadlessBackendData>>(&display.handle());
+    output.change_current_state(Some(mode), None, None, Some((0, 0).into()));
+    output.set_preferred(mode);
+
+    space.map_output(&output, (0, 0));
+
+    let damage_tracked_renderer = DamageTrackedRenderer::from_output(&output);
+
+    HeadlessBackendData {
+        renderer,
+        buffer,
+        damage_tracked_renderer,
+        output,
+        mode,
+    }
+}
+
+impl Backend for HeadlessBackendData {
+    fn init(
+        event_loop: &mut EventLoop<CalloopData<Self>>,
+        display: &mut Display<State<Self>>,
+        space: &mut Space<Window>,
+    ) -> Self {
+        init_headless(event_loop, display, space)
+    }
+
+    fn draw(data: &mut CalloopData<Self>) {
+        headless_draw(data)
+    }
+
+    fn size(&self) -> Size<i32, Logical> {
+        let s = self.mode.size;
+        (s.w, s.h).into()
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+}
@@ -1,10 +1,17 @@
 use smithay::{
     desktop::{Space, Window},
+    output::Output,
     reexports::{calloop::EventLoop, wayland_server::Display}, utils::{Size, Physical, Logical},
 };
 
+use std::str::FromStr;
+
 use crate::{CalloopData, state::State};
 
+pub mod elements;
+mod font;
+pub mod headless;
+pub mod winit;
 pub mod x11;
 
 pub trait Backend: Sized + 'static {
@@ -17,4 +24,38 @@ pub trait Backend: Sized + 'static {
     fn draw(data: &mut CalloopData<Self>);
 
     fn size(&self) -> Size<i32, Logical>;
+
+    fn output(&self) -> &Output;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    X11,
+    Winit,
+    Headless,
+}
+
+impl BackendKind {
+    pub fn detect() -> Self {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            BackendKind::Winit
+        } else if std::env::var_os("DISPLAY").is_some() {
+            BackendKind::X11
+        } else {
+            BackendKind::Headless
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x11" => Ok(BackendKind::X11),
+            "winit" => Ok(BackendKind::Winit),
+            "headless" => Ok(BackendKind::Headless),
+            _ => Err(format!("unknown backend {s:?}, expected x11, winit or headless")),
+        }
+    }
 }
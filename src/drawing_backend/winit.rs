@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::{
+        renderer::damage::DamageTrackedRenderer,
+        winit::{self, WinitError, WinitEvent, WinitEventLoop, WinitGraphicsBackend},
+    },
+    desktop::{utils::send_frames_surface_tree, Space, Window},
+    input::pointer::CursorImageStatus,
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{calloop::EventLoop, wayland_server::Display},
+    utils::{Logical, Rectangle, Size, Transform},
+};
+use tracing::trace;
+
+use crate::{state::State, CalloopData};
+
+use super::{elements, Backend};
+
+pub struct WinitBackendData {
+    backend: WinitGraphicsBackend,
+    winit: WinitEventLoop,
+    damage_tracked_renderer: DamageTrackedRenderer,
+    output: Output,
+}
+
+fn winit_draw(data: &mut CalloopData<WinitBackendData>) {
+    // Events are collected first since handling them needs the whole state
+    let mut events = Vec::new();
+    let res = data
+        .state
+        .backend_data
+        .winit
+        .dispatch_new_events(|event| events.push(event));
+
+    if let Err(WinitError::WindowClosed) = res {
+        data.state.loop_signal.stop();
+        return;
+    }
+    res.unwrap();
+
+    for event in events {
+        match event {
+            WinitEvent::Resized { size, .. } => {
+                trace!("Resized to {size:?}");
+                data.state.backend_data.output.change_current_state(
+                    Some(Mode {
+                        size,
+                        refresh: 60_000,
+                    }),
+                    None,
+                    None,
+                    None,
+                );
+                // Tiled windows follow the new size of the output
+                data.state.wm.layout(&mut data.state.space);
+            }
+            WinitEvent::Input(event) => data.state.event(event),
+            _ => {}
+        }
+    }
+
+    let focused = data.state.focused_window();
+    let backend_data = &mut data.state.backend_data;
+    let backend = &mut backend_data.backend;
+    let damage_tracked_renderer = &mut backend_data.damage_tracked_renderer;
+    let output = &backend_data.output;
+
+    let size = backend.window_size().physical_size;
+    let damage = Rectangle::from_loc_and_size((0, 0), size);
+
+    backend.bind().unwrap();
+    let renderer = backend.renderer();
+    let mut elements = data.state.cursor.render_elements(
+        renderer,
+        data.state.pointer_location,
+        output,
+        data.state.start_time.elapsed(),
+    );
+    elements.extend(elements::output_elements(
+        renderer,
+        &data.state.space,
+        &mut data.state.decorations,
+        data.state.wm.decoration_style(),
+        focused.as_ref(),
+        output,
+    ));
+    damage_tracked_renderer
+        .render_output(renderer, 0, &elements, [0.5, 0.5, 0.5, 1.0])
+        .unwrap();
+    backend.submit(Some(&[damage])).unwrap();
+
+    data.state.space.elements().for_each(|window| {
+        window.send_frame(
+            output,
+            data.state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        )
+    });
+
+    if let CursorImageStatus::Surface(surface) = &data.state.cursor.status {
+        send_frames_surface_tree(
+            surface,
+            output,
+            data.state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        );
+    }
+
+    data.state.space.refresh();
+    data.display.flush_clients().unwrap();
+}
+
+pub fn init_winit(
+    _event_loop: &mut EventLoop<CalloopData<WinitBackendData>>,
+    display: &mut Display<State<WinitBackendData>>,
+    space: &mut Space<Window>,
+) -> WinitBackendData {
+    let (backend, winit) = winit::init().unwrap();
+    // The compositor draws its own cursor
+    backend.window().set_cursor_visible(false);
+
+    let mode = Mode {
+        size: backend.window_size().physical_size,
+        refresh: 60_000,
+    };
+
+    let output = Output::new(
+        "winit".into(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "NekoWM".into(),
+            model: "winit".into(),
+        },
+    );
+
+    output.create_global::<State<WinitBackendData>>(&display.handle());
+    output.change_current_state(
+        Some(mode),
+        Some(Transform::Flipped180),
+        None,
+        Some((0, 0).into()),
+    );
+    output.set_preferred(mode);
+
+    space.map_output(&output, (0, 0));
+
+    let damage_tracked_renderer = DamageTrackedRenderer::from_output(&output);
+
+    WinitBackendData {
+        backend,
+        winit,
+        damage_tracked_renderer,
+        output,
+    }
+}
+
+impl Backend for WinitBackendData {
+    fn init(
+        event_loop: &mut EventLoop<CalloopData<Self>>,
+        display: &mut Display<State<Self>>,
+        space: &mut Space<Window>,
+    ) -> Self {
+        init_winit(event_loop, display, space)
+    }
+
+    fn draw(data: &mut CalloopData<Self>) {
+        winit_draw(data)
+    }
+
+    fn size(&self) -> Size<i32, Logical> {
+        let scale = self.output.current_scale().fractional_scale();
+        self.backend
+            .window_size()
+            .physical_size
+            .to_f64()
+            .to_logical(scale)
+            .to_i32_round()
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+}
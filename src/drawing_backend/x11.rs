@@ -7,18 +7,20 @@ use smithay::{
             gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
         },
         egl::{EGLContext, EGLDisplay},
-        renderer::{damage::DamageTrackedRenderer, gles2::Gles2Renderer, element::surface::WaylandSurfaceRenderElement, Bind},
+        renderer::{damage::DamageTrackedRenderer, gles2::Gles2Renderer, Bind},
         x11::{Window as X11Window, WindowBuilder, X11Backend, X11Event, X11Surface},
     },
-    desktop::{Space, Window},
+    desktop::{utils::send_frames_surface_tree, Space, Window},
+    input::pointer::CursorImageStatus,
     output::{Output, PhysicalProperties, Subpixel},
     reexports::{calloop::EventLoop, wayland_server::Display},
     utils::{DeviceFd, Size, Physical, Logical},
 };
+use tracing::trace;
 
 use crate::{CalloopData, state::State};
 
-use super::Backend;
+use super::{elements, Backend};
 
 pub struct X11BackendData {
     window: X11Window,
@@ -29,6 +31,7 @@ pub struct X11BackendData {
 }
 
 fn x11_draw(data: &mut CalloopData<X11BackendData>) {
+    let focused = data.state.focused_window();
     let backend_data = &mut data.state.backend_data;
     let surface = &mut backend_data.surface;
     let renderer = &mut backend_data.renderer;
@@ -37,16 +40,23 @@ fn x11_draw(data: &mut CalloopData<X11BackendData>) {
 
     let (buffer, age) = surface.buffer().unwrap();
     renderer.bind(buffer).unwrap();
-    smithay::desktop::space::render_output::<_, WaylandSurfaceRenderElement<Gles2Renderer>, _, _>(
+    let mut elements = data.state.cursor.render_elements(
+        renderer,
+        data.state.pointer_location,
         output,
+        data.state.start_time.elapsed(),
+    );
+    elements.extend(elements::output_elements(
         renderer,
-        0,
-        [&data.state.space],
-        &[],
-        damage_tracked_renderer,
-        [0.5, 0.5, 0.5, 1.0],
-    )
-    .unwrap();
+        &data.state.space,
+        &mut data.state.decorations,
+        data.state.wm.decoration_style(),
+        focused.as_ref(),
+        output,
+    ));
+    damage_tracked_renderer
+        .render_output(renderer, 0, &elements, [0.5, 0.5, 0.5, 1.0])
+        .unwrap();
     surface.submit().unwrap();
 
     data.state.space.elements().for_each(|window| {
@@ -58,6 +68,16 @@ fn x11_draw(data: &mut CalloopData<X11BackendData>) {
         )
     });
 
+    if let CursorImageStatus::Surface(surface) = &data.state.cursor.status {
+        send_frames_surface_tree(
+            surface,
+            output,
+            data.state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        );
+    }
+
     data.state.space.refresh();
     data.display.flush_clients().unwrap();
 }
@@ -73,6 +93,8 @@ pub fn init_x11(
         .title("NekoWM")
         .build(&x_handle)
         .unwrap();
+    // The compositor draws its own cursor
+    window.set_cursor_visible(false);
     let (_drm_node, fd) = x_handle.drm_node().unwrap();
     let device = GbmDevice::new(DeviceFd::from(fd)).unwrap();
     let egl = EGLDisplay::new(device.clone()).unwrap();
@@ -133,7 +155,19 @@ pub fn init_x11(
                 new_size,
                 window_id,
             } => {
-                println!("Resized!");
+                trace!("Resized to {new_size:?}");
+                let size = (new_size.w.into(), new_size.h.into()).into();
+                data.state.backend_data.output.change_current_state(
+                    Some(smithay::output::Mode {
+                        size,
+                        refresh: 60_000,
+                    }),
+                    None,
+                    None,
+                    None,
+                );
+                // Tiled windows follow the new size of the output
+                data.state.wm.layout(&mut data.state.space);
             }
             X11Event::PresentCompleted { window_id } => {}
             X11Event::CloseRequested { window_id } => {
@@ -168,4 +202,8 @@ impl Backend for X11BackendData {
         let s = self.window.size();
         (s.w.into(), s.h.into()).into()
     }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
 }
@@ -0,0 +1,37 @@
+use smithay::{
+    input::{pointer::GrabStartData as PointerGrabStartData, Seat},
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    utils::Serial,
+};
+
+use crate::{drawing_backend::Backend, state::State};
+
+pub mod move_grab;
+pub mod resize_grab;
+
+pub use move_grab::MoveSurfaceGrab;
+pub use resize_grab::ResizeSurfaceGrab;
+
+pub const BTN_LEFT: u32 = 0x110;
+
+// Returns the start data of the pointer grab that belongs to `serial`, if the
+// client owning `surface` is the one that has the pointer focus
+pub fn check_grab<B: Backend>(
+    seat: &Seat<State<B>>,
+    surface: &WlSurface,
+    serial: Serial,
+) -> Option<PointerGrabStartData<State<B>>> {
+    let pointer = seat.get_pointer()?;
+
+    if !pointer.has_grab(serial) {
+        return None;
+    }
+
+    let start_data = pointer.grab_start_data()?;
+    let (focus, _) = start_data.focus.as_ref()?;
+    if !focus.id().same_client_as(&surface.id()) {
+        return None;
+    }
+
+    Some(start_data)
+}
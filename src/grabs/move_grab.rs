@@ -0,0 +1,82 @@
+use smithay::{
+    desktop::Window,
+    input::pointer::{
+        AxisFrame, ButtonEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
+        PointerInnerHandle,
+    },
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Rectangle},
+};
+
+use super::BTN_LEFT;
+use crate::{cursor::GrabIcon, drawing_backend::Backend, state::State};
+
+// How far a tiled window has to be dragged before it floats, in logical pixels
+const DRAG_THRESHOLD: f64 = 8.0;
+
+pub struct MoveSurfaceGrab<B: Backend> {
+    pub start_data: PointerGrabStartData<State<B>>,
+    pub window: Window,
+    pub initial_window_location: Point<i32, Logical>,
+    pub _icon: GrabIcon,
+}
+
+impl<B: Backend> PointerGrab<State<B>> for MoveSurfaceGrab<B> {
+    fn motion(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // No focus while the grab is active
+        handle.motion(data, None, event);
+
+        let delta = event.location - self.start_data.location;
+        // Tiled windows stay in the layout until they are really dragged, so a
+        // click on the title bar only focuses them
+        if !data.wm.is_floating(&self.window) {
+            if delta.x.abs().max(delta.y.abs()) < DRAG_THRESHOLD {
+                return;
+            }
+            data.wm.float_in_place(&self.window, &mut data.space);
+        }
+        let new_location = self.initial_window_location.to_f64() + delta;
+        data.space
+            .map_element(self.window.clone(), new_location.to_i32_round(), true);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        if !handle.current_pressed().contains(&BTN_LEFT) {
+            handle.unset_grab(data, event.serial, event.time);
+
+            if !data.wm.is_floating(&self.window) {
+                return;
+            }
+            if let Some(loc) = data.space.element_location(&self.window) {
+                let geometry = Rectangle::from_loc_and_size(loc, self.window.geometry().size);
+                data.wm.window_moved(&self.window, geometry);
+            }
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<State<B>> {
+        &self.start_data
+    }
+}
@@ -0,0 +1,257 @@
+use std::cell::RefCell;
+
+use smithay::{
+    desktop::{Space, Window},
+    input::pointer::{
+        AxisFrame, ButtonEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
+        PointerInnerHandle,
+    },
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge},
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    utils::{Logical, Point, Rectangle, Size},
+    wayland::{compositor, shell::xdg::SurfaceCachedState},
+};
+
+use super::BTN_LEFT;
+use crate::{cursor::GrabIcon, drawing_backend::Backend, state::State};
+
+fn has_edge(edges: ResizeEdge, edge: ResizeEdge) -> bool {
+    edges as u32 & edge as u32 != 0
+}
+
+pub struct ResizeSurfaceGrab<B: Backend> {
+    start_data: PointerGrabStartData<State<B>>,
+    window: Window,
+    edges: ResizeEdge,
+    initial_rect: Rectangle<i32, Logical>,
+    last_window_size: Size<i32, Logical>,
+    _icon: GrabIcon,
+}
+
+impl<B: Backend> ResizeSurfaceGrab<B> {
+    pub fn start(
+        start_data: PointerGrabStartData<State<B>>,
+        window: Window,
+        edges: ResizeEdge,
+        initial_rect: Rectangle<i32, Logical>,
+        icon: GrabIcon,
+    ) -> Self {
+        ResizeSurfaceState::with(window.toplevel().wl_surface(), |state| {
+            *state = ResizeSurfaceState::Resizing {
+                edges,
+                initial_rect,
+            };
+        });
+
+        Self {
+            start_data,
+            window,
+            edges,
+            initial_rect,
+            last_window_size: initial_rect.size,
+            _icon: icon,
+        }
+    }
+}
+
+impl<B: Backend> PointerGrab<State<B>> for ResizeSurfaceGrab<B> {
+    fn motion(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // No focus while the grab is active
+        handle.motion(data, None, event);
+
+        let mut delta = event.location - self.start_data.location;
+
+        let mut new_window_width = self.initial_rect.size.w;
+        let mut new_window_height = self.initial_rect.size.h;
+
+        if has_edge(self.edges, ResizeEdge::Left) || has_edge(self.edges, ResizeEdge::Right) {
+            if has_edge(self.edges, ResizeEdge::Left) {
+                delta.x = -delta.x;
+            }
+            new_window_width = (self.initial_rect.size.w as f64 + delta.x) as i32;
+        }
+
+        if has_edge(self.edges, ResizeEdge::Top) || has_edge(self.edges, ResizeEdge::Bottom) {
+            if has_edge(self.edges, ResizeEdge::Top) {
+                delta.y = -delta.y;
+            }
+            new_window_height = (self.initial_rect.size.h as f64 + delta.y) as i32;
+        }
+
+        let (min_size, max_size) =
+            compositor::with_states(self.window.toplevel().wl_surface(), |states| {
+                let data = states.cached_state.current::<SurfaceCachedState>();
+                (data.min_size, data.max_size)
+            });
+
+        // A zero max size means the client has no limit
+        let min_width = min_size.w.max(1);
+        let min_height = min_size.h.max(1);
+        let max_width = if max_size.w == 0 {
+            i32::MAX
+        } else {
+            max_size.w
+        };
+        let max_height = if max_size.h == 0 {
+            i32::MAX
+        } else {
+            max_size.h
+        };
+
+        new_window_width = new_window_width.clamp(min_width, max_width.max(min_width));
+        new_window_height = new_window_height.clamp(min_height, max_height.max(min_height));
+
+        self.last_window_size = (new_window_width, new_window_height).into();
+
+        let toplevel = self.window.toplevel();
+        toplevel.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Resizing);
+            state.size = Some(self.last_window_size);
+        });
+        toplevel.send_configure();
+    }
+
+    fn button(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        if !handle.current_pressed().contains(&BTN_LEFT) {
+            handle.unset_grab(data, event.serial, event.time);
+
+            let toplevel = self.window.toplevel();
+            toplevel.with_pending_state(|state| {
+                state.states.unset(xdg_toplevel::State::Resizing);
+                state.size = Some(self.last_window_size);
+            });
+            toplevel.send_configure();
+
+            ResizeSurfaceState::with(toplevel.wl_surface(), |state| {
+                *state = ResizeSurfaceState::WaitingForLastCommit {
+                    edges: self.edges,
+                    initial_rect: self.initial_rect,
+                };
+            });
+
+            // Where the window ends up once the client commits the last size
+            let mut geometry =
+                Rectangle::from_loc_and_size(self.initial_rect.loc, self.last_window_size);
+            if has_edge(self.edges, ResizeEdge::Left) {
+                geometry.loc.x += self.initial_rect.size.w - self.last_window_size.w;
+            }
+            if has_edge(self.edges, ResizeEdge::Top) {
+                geometry.loc.y += self.initial_rect.size.h - self.last_window_size.h;
+            }
+            data.wm.window_moved(&self.window, geometry);
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut State<B>,
+        handle: &mut PointerInnerHandle<'_, State<B>>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details)
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<State<B>> {
+        &self.start_data
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum ResizeSurfaceState {
+    #[default]
+    Idle,
+    Resizing {
+        edges: ResizeEdge,
+        initial_rect: Rectangle<i32, Logical>,
+    },
+    // The grab is released, but the client may still commit the last size
+    WaitingForLastCommit {
+        edges: ResizeEdge,
+        initial_rect: Rectangle<i32, Logical>,
+    },
+}
+
+impl ResizeSurfaceState {
+    fn with<F, T>(surface: &WlSurface, cb: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        compositor::with_states(surface, |states| {
+            states.data_map.insert_if_missing(RefCell::<Self>::default);
+            let state = states.data_map.get::<RefCell<Self>>().unwrap();
+
+            cb(&mut state.borrow_mut())
+        })
+    }
+
+    fn commit(&mut self) -> Option<(ResizeEdge, Rectangle<i32, Logical>)> {
+        match *self {
+            Self::Resizing {
+                edges,
+                initial_rect,
+            } => Some((edges, initial_rect)),
+            Self::WaitingForLastCommit {
+                edges,
+                initial_rect,
+            } => {
+                *self = Self::Idle;
+                Some((edges, initial_rect))
+            }
+            Self::Idle => None,
+        }
+    }
+}
+
+// Resizing from the top or left edge moves the window, which can only be done
+// once the client has committed a buffer with the new size
+pub fn handle_commit(space: &mut Space<Window>, surface: &WlSurface) -> Option<()> {
+    let window = space
+        .elements()
+        .find(|w| w.toplevel().wl_surface() == surface)
+        .cloned()?;
+
+    let mut window_loc = space.element_location(&window)?;
+    let geometry = window.geometry();
+
+    let new_loc: Point<Option<i32>, Logical> = ResizeSurfaceState::with(surface, |state| {
+        state
+            .commit()
+            .map(|(edges, initial_rect)| {
+                let new_x = has_edge(edges, ResizeEdge::Left)
+                    .then_some(initial_rect.loc.x + (initial_rect.size.w - geometry.size.w));
+                let new_y = has_edge(edges, ResizeEdge::Top)
+                    .then_some(initial_rect.loc.y + (initial_rect.size.h - geometry.size.h));
+                (new_x, new_y).into()
+            })
+            .unwrap_or_default()
+    });
+
+    if new_loc.x.is_none() && new_loc.y.is_none() {
+        return Some(());
+    }
+
+    if let Some(new_x) = new_loc.x {
+        window_loc.x = new_x;
+    }
+    if let Some(new_y) = new_loc.y {
+        window_loc.y = new_y;
+    }
+    space.map_element(window, window_loc, false);
+
+    Some(())
+}
@@ -1,17 +1,22 @@
 use smithay::{
-    backend::input::{Event, InputBackend, InputEvent, KeyboardKeyEvent},
+    backend::input::{
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
+        KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+    },
     delegate_seat,
+    desktop::WindowSurfaceType,
     input::{
         keyboard::{FilterResult, KeyboardTarget},
-        pointer::CursorImageStatus,
+        pointer::{AxisFrame, ButtonEvent, CursorImageStatus, MotionEvent},
         Seat, SeatHandler, SeatState,
     },
-    reexports::{wayland_server::protocol::wl_surface::WlSurface, wayland_protocols::xdg::shell::server::xdg_toplevel},
-    utils::SERIAL_COUNTER,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Serial, SERIAL_COUNTER},
     wayland::shell::xdg::ToplevelSurface,
 };
+use tracing::trace;
 
-use crate::{drawing_backend::Backend, state::State, CalloopData};
+use crate::{bindings::{Action, KeyResult}, drawing_backend::Backend, state::State, CalloopData};
 
 impl<B: Backend> State<B> {
     pub fn event<I: InputBackend>(&mut self, event: InputEvent<I>) {
@@ -19,28 +24,186 @@ impl<B: Backend> State<B> {
             InputEvent::Keyboard { event } => {
                 let serial = smithay::utils::SERIAL_COUNTER.next_serial();
                 let time = Event::time_msec(&event);
+                let keycode = event.key_code();
+                let key_state = event.state();
 
-                for (i, elem) in self.space.elements().enumerate() {
-                    if elem.toplevel().current_state().states.contains(xdg_toplevel::State::Activated) {
-                        print!("{i}.");
-                    } else {
-                        print!("{i}!");
-                    }
+                match key_state {
+                    KeyState::Pressed => self.pressed_keys.push(keycode),
+                    KeyState::Released => self.pressed_keys.retain(|k| *k != keycode),
                 }
-                println!();
 
-                self.seat.get_keyboard().unwrap().input::<(), _>(
+                let keyboard = self.seat.get_keyboard().unwrap();
+                let action = keyboard.input::<Option<Action>, _>(
                     self,
-                    event.key_code(),
-                    event.state(),
+                    keycode,
+                    key_state,
                     serial,
                     time,
-                    |a, b, c| FilterResult::Forward,
+                    |state, modifiers, handle| match key_state {
+                        KeyState::Pressed => {
+                            let action = match state.bindings.press(modifiers, handle.raw_syms()) {
+                                KeyResult::Action(action) => Some(action),
+                                KeyResult::Pending => {
+                                    state.start_chord_timer();
+                                    None
+                                }
+                                KeyResult::Aborted => None,
+                                KeyResult::Unbound => return FilterResult::Forward,
+                            };
+                            state.suppressed_keys.push(keycode);
+                            FilterResult::Intercept(action)
+                        }
+                        KeyState::Released => {
+                            match state.suppressed_keys.iter().position(|k| *k == keycode) {
+                                Some(index) => {
+                                    state.suppressed_keys.remove(index);
+                                    FilterResult::Intercept(None)
+                                }
+                                None => FilterResult::Forward,
+                            }
+                        }
+                    },
+                );
+                if let Some(Some(action)) = action {
+                    self.run_action(action);
+                }
+            }
+            InputEvent::PointerMotion { event } => {
+                let location = self.pointer_location + event.delta();
+                self.pointer_moved(location, Event::time_msec(&event));
+            }
+            InputEvent::PointerMotionAbsolute { event } => {
+                let Some(output) = self.space.output_geometry(self.backend_data.output()) else {
+                    return;
+                };
+                let location = output.loc.to_f64() + event.position_transformed(output.size);
+                self.pointer_moved(location, Event::time_msec(&event));
+            }
+            InputEvent::PointerButton { event } => {
+                let serial = SERIAL_COUNTER.next_serial();
+                let button = event.button_code();
+                let pointer = self.seat.get_pointer().unwrap();
+
+                if event.state() == ButtonState::Pressed
+                    && !pointer.is_grabbed()
+                    && let Some((window, hit)) = self.decoration_under(self.pointer_location)
+                {
+                    self.decoration_clicked(window, hit, button, serial);
+                    // A grab started by the click gets the release to end it
+                    if !pointer.is_grabbed() {
+                        self.suppressed_buttons.push(button);
+                    }
+                    return;
+                }
+                if event.state() == ButtonState::Released
+                    && let Some(index) = self.suppressed_buttons.iter().position(|b| *b == button)
+                {
+                    self.suppressed_buttons.remove(index);
+                    return;
+                }
+
+                // Click to focus
+                if event.state() == ButtonState::Pressed
+                    && !pointer.is_grabbed()
+                    && let Some((window, _)) = self.space.element_under(self.pointer_location)
+                {
+                    let window = window.clone();
+                    self.set_focus(Some(window));
+                }
+
+                pointer.button(
+                    self,
+                    &ButtonEvent {
+                        button,
+                        state: event.state(),
+                        serial,
+                        time: Event::time_msec(&event),
+                    },
                 );
+                // Ending a grab enters the surface under the pointer again
+                self.pointer_entered(serial);
+            }
+            InputEvent::PointerAxis { event } => {
+                let source = event.source();
+                let mut frame = AxisFrame::new(Event::time_msec(&event)).source(source);
+                for axis in [Axis::Horizontal, Axis::Vertical] {
+                    let discrete = event.amount_discrete(axis);
+                    let amount = event
+                        .amount(axis)
+                        .unwrap_or_else(|| discrete.unwrap_or(0.0) * 3.0);
+                    if amount != 0.0 {
+                        frame = frame.value(axis, amount);
+                        if let Some(discrete) = discrete {
+                            frame = frame.discrete(axis, discrete as i32);
+                        }
+                    } else if source == AxisSource::Finger {
+                        frame = frame.stop(axis);
+                    }
+                }
+                self.seat.get_pointer().unwrap().axis(self, frame);
             }
             _ => {}
         }
     }
+
+    // The client surface under `point` and where it is, frames belong to no surface
+    pub fn surface_under(
+        &self,
+        point: Point<f64, Logical>,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        if self.decoration_under(point).is_some() {
+            return None;
+        }
+        let (window, location) = self.space.element_under(point)?;
+        window
+            .surface_under(point - location.to_f64(), WindowSurfaceType::ALL)
+            .map(|(surface, offset)| (surface, offset + location))
+    }
+
+    fn pointer_moved(&mut self, location: Point<f64, Logical>, time: u32) {
+        // Kept on the output
+        if let Some(output) = self.space.output_geometry(self.backend_data.output()) {
+            let (x, y) = (output.loc.x as f64, output.loc.y as f64);
+            let (w, h) = (output.size.w as f64, output.size.h as f64);
+            self.pointer_location = (
+                location.x.clamp(x, x + w - 1.0),
+                location.y.clamp(y, y + h - 1.0),
+            )
+                .into();
+        }
+
+        let serial = SERIAL_COUNTER.next_serial();
+        let under = self.surface_under(self.pointer_location);
+        let pointer = self.seat.get_pointer().unwrap();
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: self.pointer_location,
+                serial,
+                time,
+            },
+        );
+        self.pointer_entered(serial);
+    }
+
+    // Remembers the serial of the wl_pointer.enter sent along with `serial`, if
+    // it changed the pointer focus. Grabs keep the focus cleared.
+    fn pointer_entered(&mut self, serial: Serial) {
+        let focus = if self.seat.get_pointer().unwrap().is_grabbed() {
+            None
+        } else {
+            self.surface_under(self.pointer_location)
+                .map(|(surface, _)| surface)
+        };
+        if self.pointer_enter.as_ref().map(|(surface, _)| surface) != focus.as_ref() {
+            // Whatever the last client picked doesn't apply over frames and the background
+            if focus.is_none() {
+                self.cursor.set_status(CursorImageStatus::Default);
+            }
+            self.pointer_enter = focus.map(|surface| (surface, serial));
+        }
+    }
 }
 
 impl<B: Backend> SeatHandler for State<B> {
@@ -53,10 +216,19 @@ impl<B: Backend> SeatHandler for State<B> {
     }
 
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&Self::KeyboardFocus>) {
-        println!("Focus changed to {focused:?}");
+        trace!("Focus changed to {focused:?}");
+        let window = focused.and_then(|surface| self.wm.window_for_surface(surface));
+        if let Some(window) = &window
+            && self.wm.focus_changed(window)
+        {
+            self.wm.layout(&mut self.space);
+        }
+        self.keyboard_focus = window;
     }
 
-    fn cursor_image(&mut self, _seat: &Seat<Self>, _image: CursorImageStatus) {}
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
+        self.cursor.set_status(image);
+    }
 }
 
 delegate_seat!(@<B: Backend> State<B>);
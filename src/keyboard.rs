@@ -0,0 +1,310 @@
+use std::{cell::RefCell, path::PathBuf};
+
+use smithay::{
+    backend::input::KeyState,
+    desktop::Window,
+    input::keyboard::{FilterResult, XkbConfig},
+    utils::{IsAlive, SERIAL_COUNTER},
+    wayland::compositor::with_states,
+};
+use tracing::{debug, warn};
+
+use crate::{drawing_backend::Backend, state::State};
+
+// Empty strings let xkbcommon pick its defaults
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardConfig {
+    pub rules: String,
+    pub model: String,
+    // Comma separated, like "us,se", each one gets a keymap of its own
+    pub layout: String,
+    // Comma separated, one for each layout
+    pub variant: String,
+    // Like "ctrl:nocaps", group switching options have nothing to switch
+    pub options: Option<String>,
+    // In milliseconds
+    pub repeat_delay: i32,
+    // Repeats per second
+    pub repeat_rate: i32,
+    // Each window keeps the layout it had when it lost focus
+    pub per_window_layout: bool,
+}
+
+impl Default for KeyboardConfig {
+    fn default() -> Self {
+        Self {
+            rules: String::new(),
+            model: String::new(),
+            layout: String::new(),
+            variant: String::new(),
+            options: None,
+            repeat_delay: 200,
+            repeat_rate: 25,
+            per_window_layout: false,
+        }
+    }
+}
+
+impl KeyboardConfig {
+    // The usual XKB_DEFAULT_* variables, plus NEKOWM_REPEAT_DELAY, NEKOWM_REPEAT_RATE
+    // and NEKOWM_PER_WINDOW_LAYOUT
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let number = |name, default| match std::env::var(name) {
+            Ok(s) => s.parse().unwrap_or_else(|_| {
+                warn!("Invalid {name} {s:?}, using {default}");
+                default
+            }),
+            Err(_) => default,
+        };
+
+        let default = Self::default();
+        Self {
+            rules: var("XKB_DEFAULT_RULES"),
+            model: var("XKB_DEFAULT_MODEL"),
+            layout: var("XKB_DEFAULT_LAYOUT"),
+            variant: var("XKB_DEFAULT_VARIANT"),
+            options: std::env::var("XKB_DEFAULT_OPTIONS").ok(),
+            repeat_delay: number("NEKOWM_REPEAT_DELAY", default.repeat_delay),
+            repeat_rate: number("NEKOWM_REPEAT_RATE", default.repeat_rate),
+            per_window_layout: std::env::var("NEKOWM_PER_WINDOW_LAYOUT")
+                .is_ok_and(|s| is_enabled(&s)),
+        }
+    }
+
+    // The environment, overridden by $XDG_CONFIG_HOME/nekowm/keyboard if it
+    // exists. Read again on ReloadKeyboardConfig.
+    pub fn load() -> Self {
+        let mut config = Self::from_env();
+        let Some(path) = config_file() else {
+            return config;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => config.apply_file(&contents),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!("Could not read {path:?}: {err}"),
+        }
+        config
+    }
+
+    // One "field = value" per line, named like the fields here, # starts a comment
+    fn apply_file(&mut self, contents: &str) {
+        let lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let Some((key, value)) = line.split_once('=') else {
+                warn!("Invalid keyboard config line {line:?}");
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let number = |current: i32| {
+                value.parse().unwrap_or_else(|_| {
+                    warn!("Invalid {key} {value:?}, using {current}");
+                    current
+                })
+            };
+            match key {
+                "rules" => self.rules = value.to_owned(),
+                "model" => self.model = value.to_owned(),
+                "layout" => self.layout = value.to_owned(),
+                "variant" => self.variant = value.to_owned(),
+                "options" => self.options = Some(value.to_owned()),
+                "repeat_delay" => self.repeat_delay = number(self.repeat_delay),
+                "repeat_rate" => self.repeat_rate = number(self.repeat_rate),
+                "per_window_layout" => self.per_window_layout = is_enabled(value),
+                _ => warn!("Unknown keyboard config field {key}"),
+            }
+        }
+    }
+
+    // The layouts with their variants, like [("us", ""), ("se", "nodeadkeys")]
+    pub fn layouts(&self) -> Vec<(&str, &str)> {
+        let mut variants = self.variant.split(',');
+        self.layout
+            .split(',')
+            .map(|layout| (layout.trim(), variants.next().unwrap_or("").trim()))
+            .collect()
+    }
+
+    // The keymap for one of the layouts
+    pub fn xkb_config(&self, layout: u32) -> XkbConfig<'_> {
+        let (layout, variant) = self
+            .layouts()
+            .get(layout as usize)
+            .copied()
+            .unwrap_or_default();
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout,
+            variant,
+            options: self.options.clone(),
+        }
+    }
+
+    fn same_keymap(&self, other: &Self) -> bool {
+        self.rules == other.rules
+            && self.model == other.model
+            && self.layout == other.layout
+            && self.variant == other.variant
+            && self.options == other.options
+    }
+}
+
+fn is_enabled(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes")
+}
+
+fn config_file() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+    Some(dir.join("nekowm").join("keyboard"))
+}
+
+// The layout a window had when it last lost focus
+#[derive(Default)]
+struct WindowLayout(RefCell<Option<u32>>);
+
+impl WindowLayout {
+    fn get(window: &Window) -> Option<u32> {
+        with_states(window.toplevel().wl_surface(), |states| {
+            states.data_map.insert_if_missing(WindowLayout::default);
+            *states.data_map.get::<WindowLayout>().unwrap().0.borrow()
+        })
+    }
+
+    fn set(window: &Window, layout: u32) {
+        with_states(window.toplevel().wl_surface(), |states| {
+            states.data_map.insert_if_missing(WindowLayout::default);
+            *states
+                .data_map
+                .get::<WindowLayout>()
+                .unwrap()
+                .0
+                .borrow_mut() = Some(layout);
+        })
+    }
+}
+
+impl<B: Backend> State<B> {
+    pub fn keyboard_config(&self) -> &KeyboardConfig {
+        &self.keyboard_config
+    }
+
+    // Clients get the new keymap and repeat info right away. A new keymap
+    // replaces the seat keyboard, so every client sees the keyboard unplugged
+    // and plugged back in. On a bad keymap nothing changes and false is
+    // returned. The active layout is kept if the new config still has it.
+    pub fn set_keyboard_config(&mut self, config: KeyboardConfig) -> bool {
+        if config.same_keymap(&self.keyboard_config) {
+            let keyboard = self.seat.get_keyboard().unwrap();
+            keyboard.change_repeat_info(config.repeat_rate, config.repeat_delay);
+        } else {
+            let kept = self.keyboard_layout < config.layouts().len() as u32;
+            let layout = if kept { self.keyboard_layout } else { 0 };
+            if !self.load_keyboard(&config, layout) {
+                return false;
+            }
+            self.keyboard_layout = layout;
+        }
+        debug!("Keyboard config is now {config:?}");
+        self.keyboard_config = config;
+        true
+    }
+
+    // The keymap of a keyboard can't change, so a new keyboard replaces it and
+    // clients see the old one unplugged. Held keys and the focus carry over.
+    fn load_keyboard(&mut self, config: &KeyboardConfig, layout: u32) -> bool {
+        let focus = self.seat.get_keyboard().unwrap().current_focus();
+        let keyboard = match self.seat.add_keyboard(
+            config.xkb_config(layout),
+            config.repeat_delay,
+            config.repeat_rate,
+        ) {
+            Ok(keyboard) => keyboard,
+            Err(err) => {
+                warn!("Could not load keymap {config:?}: {err:?}");
+                return false;
+            }
+        };
+
+        // Only the new keymap sees them again, clients get them with the enter
+        let serial = SERIAL_COUNTER.next_serial();
+        let time = self.start_time.elapsed().as_millis() as u32;
+        let held = self
+            .pressed_keys
+            .iter()
+            .filter(|keycode| !self.suppressed_keys.contains(keycode))
+            .copied()
+            .collect::<Vec<_>>();
+        for keycode in held {
+            keyboard.input::<(), _>(self, keycode, KeyState::Pressed, serial, time, |_, _, _| {
+                FilterResult::Intercept(())
+            });
+        }
+        keyboard.set_focus(self, focus, serial);
+        true
+    }
+
+    // The index of the active layout among the configured ones
+    pub fn active_keyboard_layout(&self) -> u32 {
+        self.keyboard_layout
+    }
+
+    pub fn keyboard_layout_count(&self) -> u32 {
+        self.keyboard_config.layouts().len() as u32
+    }
+
+    pub fn set_keyboard_layout(&mut self, layout: u32) {
+        let count = self.keyboard_layout_count();
+        if layout >= count {
+            warn!("No keyboard layout {layout}, there are {count}");
+            return;
+        }
+        if layout == self.keyboard_layout {
+            return;
+        }
+        let config = self.keyboard_config.clone();
+        if self.load_keyboard(&config, layout) {
+            self.keyboard_layout = layout;
+            debug!("Keyboard layout is now {layout}");
+        }
+    }
+
+    pub fn cycle_keyboard_layout(&mut self, forward: bool) {
+        let count = self.keyboard_layout_count();
+        if count < 2 {
+            return;
+        }
+        let active = self.active_keyboard_layout();
+        let layout = if forward {
+            (active + 1) % count
+        } else {
+            (active + count - 1) % count
+        };
+        self.set_keyboard_layout(layout);
+    }
+
+    // Saves the layout of the window that lost focus and brings back the one of
+    // the window that got it, new windows start out with the first layout.
+    // Changing the layout replaces the keyboard, so this runs once the seat is
+    // done changing focus rather than from the focus callback.
+    pub(crate) fn keyboard_focus_changed(&mut self) {
+        let window = self.keyboard_focus.clone();
+        if !self.keyboard_config.per_window_layout || self.layout_window == window {
+            return;
+        }
+        if let Some(previous) = self.layout_window.take()
+            && previous.alive()
+        {
+            WindowLayout::set(&previous, self.keyboard_layout);
+        }
+        if let Some(window) = window {
+            self.set_keyboard_layout(WindowLayout::get(&window).unwrap_or(0));
+            self.layout_window = Some(window);
+        }
+    }
+}
@@ -1,20 +1,27 @@
 #![feature(let_chains)]
 
+pub mod bindings;
 pub mod compositor;
+pub mod cursor;
+pub mod cursor_shape;
 pub mod data_device;
 pub mod decorator;
 pub mod drawing_backend;
+pub mod grabs;
 mod input;
+pub mod keyboard;
 pub mod shell;
 pub mod state;
 pub mod window_manager;
 
+use drawing_backend::headless::HeadlessBackendData;
+use drawing_backend::winit::WinitBackendData;
 use drawing_backend::x11::X11BackendData;
-use drawing_backend::Backend;
+use drawing_backend::{Backend, BackendKind};
 use smithay::reexports::{calloop::EventLoop, wayland_server::Display};
 use state::State;
 use std::time::Duration;
-use tracing::metadata::LevelFilter;
+use tracing::{info, metadata::LevelFilter};
 
 pub struct CalloopData<B: Backend> {
     state: State<B>,
@@ -26,12 +33,48 @@ fn main() {
         .with_max_level(LevelFilter::INFO)
         .init();
 
-    let mut event_loop: EventLoop<CalloopData<X11BackendData>> = EventLoop::try_new().unwrap();
-    let mut display: Display<State<X11BackendData>> = Display::new().unwrap();
+    let mut backend = None;
+    let mut command = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--backend" {
+            backend = Some(args.next().unwrap_or_else(|| exit_with("--backend requires a value")));
+        } else if let Some(value) = arg.strip_prefix("--backend=") {
+            backend = Some(value.to_owned());
+        } else if arg.starts_with('-') {
+            exit_with(&format!("Unknown option {arg}"));
+        } else if command.is_none() {
+            command = Some(arg);
+        }
+    }
+
+    let backend = match backend.or_else(|| std::env::var("NEKOWM_BACKEND").ok()) {
+        Some(name) => name
+            .parse::<BackendKind>()
+            .unwrap_or_else(|err: String| exit_with(&err)),
+        None => BackendKind::detect(),
+    };
+    info!("Using {backend:?} backend");
+
+    match backend {
+        BackendKind::X11 => run::<X11BackendData>(command),
+        BackendKind::Winit => run::<WinitBackendData>(command),
+        BackendKind::Headless => run::<HeadlessBackendData>(command),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("nekowm: {message}");
+    std::process::exit(1);
+}
+
+fn run<B: Backend>(command: Option<String>) {
+    let mut event_loop: EventLoop<CalloopData<B>> = EventLoop::try_new().unwrap();
+    let mut display: Display<State<B>> = Display::new().unwrap();
     let state = State::new(&mut event_loop, &mut display);
     let mut data = CalloopData { state, display };
 
-    if let Some(command) = std::env::args().skip(1).next() {
+    if let Some(command) = command {
         std::process::Command::new(&command).spawn().unwrap();
     }
 
@@ -40,8 +83,10 @@ fn main() {
             Duration::from_secs_f32(1.0 / 60.0),
             &mut data,
             move |data| {
-                X11BackendData::draw(data);
+                B::draw(data);
             },
         )
         .unwrap();
+
+    data.state.remove_mode_file();
 }
@@ -1,6 +1,7 @@
-use smithay::{wayland::shell::xdg::{XdgShellHandler, XdgShellState, ToplevelSurface, PopupSurface, PositionerState, ShellClient, Configure}, utils::{SERIAL_COUNTER, Serial, Point, Logical}, desktop::Window, delegate_xdg_shell, reexports::{wayland_server::protocol::{wl_seat::WlSeat, wl_output::WlOutput, wl_surface::WlSurface}, wayland_protocols::xdg::shell::server::xdg_toplevel::ResizeEdge}};
+use smithay::{wayland::shell::xdg::{XdgShellHandler, XdgShellState, ToplevelSurface, PopupSurface, PositionerState, ShellClient, Configure}, utils::{Serial, Point, Logical, Rectangle}, desktop::Window, delegate_xdg_shell, reexports::{wayland_server::protocol::{wl_seat::WlSeat, wl_output::WlOutput, wl_surface::WlSurface}, wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge}}, input::{Seat, pointer::{Focus, GrabStartData as PointerGrabStartData}}};
+use tracing::trace;
 
-use crate::{state::State, drawing_backend::Backend};
+use crate::{cursor, state::State, drawing_backend::Backend, grabs::{self, MoveSurfaceGrab, ResizeSurfaceGrab}, window_manager::send_configure_if_ready};
 
 impl<B: Backend> XdgShellHandler for State<B> {
     fn xdg_shell_state(&mut self) -> &mut XdgShellState {
@@ -8,14 +9,10 @@ impl<B: Backend> XdgShellHandler for State<B> {
     }
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
-        let serial = SERIAL_COUNTER.next_serial();
-        self.seat.get_keyboard().unwrap().set_focus(
-            self,
-            Some(surface.wl_surface().clone()),
-            serial,
-        );
         let window = Window::new(surface);
-        self.space.map_element(window, (0, 0), true);
+        self.space.map_element(window.clone(), (0, 0), true);
+        self.wm.toplevel_created(&window, &mut self.space);
+        self.set_focus(Some(window));
         for elem in self.space.elements() {
             let toplevel = elem.toplevel();
             let current_state = toplevel.current_state();
@@ -47,7 +44,21 @@ impl<B: Backend> XdgShellHandler for State<B> {
 
     fn client_pong(&mut self, client: ShellClient) {}
 
-    fn move_request(&mut self, surface: ToplevelSurface, seat: WlSeat, serial: Serial) {}
+    fn move_request(&mut self, surface: ToplevelSurface, seat: WlSeat, serial: Serial) {
+        let seat = Seat::from_resource(&seat).unwrap();
+        let wl_surface = surface.wl_surface();
+
+        let Some(window) = self.mapped_window_for_surface(wl_surface) else {
+            return;
+        };
+        if !self.wm.is_floating(&window) {
+            return;
+        }
+
+        if let Some(start_data) = grabs::check_grab(&seat, wl_surface, serial) {
+            self.start_move(window, start_data, serial);
+        }
+    }
 
     fn resize_request(
         &mut self,
@@ -56,17 +67,46 @@ impl<B: Backend> XdgShellHandler for State<B> {
         serial: Serial,
         edges: ResizeEdge,
     ) {
+        let seat = Seat::from_resource(&seat).unwrap();
+        let wl_surface = surface.wl_surface();
+
+        let Some(window) = self.mapped_window_for_surface(wl_surface) else {
+            return;
+        };
+        if !self.wm.is_floating(&window) {
+            return;
+        }
+
+        if let Some(start_data) = grabs::check_grab(&seat, wl_surface, serial) {
+            self.start_resize(window, edges, start_data, serial);
+        }
     }
 
-    fn maximize_request(&mut self, surface: ToplevelSurface) {}
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.wm.window_for_surface(surface.wl_surface()) {
+            self.wm.set_maximized(&window, true, &mut self.space);
+        }
+        send_configure_if_ready(&surface);
+    }
 
-    fn unmaximize_request(&mut self, surface: ToplevelSurface) {}
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.wm.window_for_surface(surface.wl_surface()) {
+            self.wm.set_maximized(&window, false, &mut self.space);
+        }
+        send_configure_if_ready(&surface);
+    }
 
     fn fullscreen_request(&mut self, surface: ToplevelSurface, output: Option<WlOutput>) {}
 
     fn unfullscreen_request(&mut self, surface: ToplevelSurface) {}
 
-    fn minimize_request(&mut self, surface: ToplevelSurface) {}
+    fn minimize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.wm.window_for_surface(surface.wl_surface()) {
+            self.wm.minimize(&window, &mut self.space);
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
+        }
+    }
 
     fn show_window_menu(
         &mut self,
@@ -82,13 +122,15 @@ impl<B: Backend> XdgShellHandler for State<B> {
     fn reposition_request(&mut self, surface: PopupSurface, positioner: PositionerState, token: u32) {}
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
-        let window = self.space.elements().find(|w| *w.toplevel() == surface).cloned();
+        let window = self.wm.window_for_surface(surface.wl_surface());
         if let Some(window) = window {
             self.space.unmap_elem(&window);
+            self.wm.toplevel_destroyed(&window, &mut self.space);
         }
-        println!("Space contains {} windows", self.space.elements().count());
+        trace!("Space contains {} windows", self.space.elements().count());
         if let Some(kb) = self.seat.get_keyboard() && kb.current_focus() == Some(surface.wl_surface().clone()) {
-            self;
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
         }
     }
 
@@ -98,3 +140,52 @@ impl<B: Backend> XdgShellHandler for State<B> {
 }
 
 delegate_xdg_shell!(@<B: Backend> State<B>);
+
+impl<B: Backend> State<B> {
+    pub fn start_move(
+        &mut self,
+        window: Window,
+        start_data: PointerGrabStartData<Self>,
+        serial: Serial,
+    ) {
+        let pointer = self.seat.get_pointer().unwrap();
+        let initial_window_location = self.space.element_location(&window).unwrap();
+
+        let grab = MoveSurfaceGrab {
+            start_data,
+            window,
+            initial_window_location,
+            _icon: self.cursor.grab_icon(cursor::MOVE_ICON),
+        };
+
+        pointer.set_grab(self, grab, serial, Focus::Clear);
+    }
+
+    pub fn start_resize(
+        &mut self,
+        window: Window,
+        edges: ResizeEdge,
+        start_data: PointerGrabStartData<Self>,
+        serial: Serial,
+    ) {
+        let pointer = self.seat.get_pointer().unwrap();
+        let initial_window_location = self.space.element_location(&window).unwrap();
+        let initial_window_size = window.geometry().size;
+
+        let surface = window.toplevel();
+        surface.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Resizing);
+        });
+        surface.send_configure();
+
+        let grab = ResizeSurfaceGrab::start(
+            start_data,
+            window,
+            edges,
+            Rectangle::from_loc_and_size(initial_window_location, initial_window_size),
+            self.cursor.grab_icon(cursor::resize_icon(edges)),
+        );
+
+        pointer.set_grab(self, grab, serial, Focus::Clear);
+    }
+}
@@ -1,11 +1,11 @@
-use std::{ffi::OsString, sync::Arc, os::fd::AsRawFd};
+use std::{ffi::OsString, path::PathBuf, sync::Arc, os::fd::AsRawFd, time::{Duration, Instant}};
 
 use smithay::{
     desktop::{Space, Window},
     input::{Seat, SeatState},
     reexports::{
-        calloop::{generic::Generic, EventLoop, Interest, LoopSignal, Mode, PostAction},
-        wayland_server::Display,
+        calloop::{generic::Generic, timer::{TimeoutAction, Timer}, EventLoop, Interest, LoopHandle, LoopSignal, Mode, PostAction},
+        wayland_server::{protocol::wl_surface::WlSurface, Display},
     },
     wayland::{
         compositor::CompositorState,
@@ -17,17 +17,20 @@ use smithay::{
         },
         shm::{ShmState, ShmHandler},
         socket::ListeningSocketSource, buffer::BufferHandler,
-    }, delegate_output, delegate_shm,
+    }, delegate_output, delegate_shm, utils::{Logical, Point, Serial, SERIAL_COUNTER},
 };
+use tracing::{debug, warn};
 
-use crate::{drawing_backend::Backend, CalloopData, window_manager::WindowManager};
+use crate::{bindings::{Action, Bindings, DEFAULT_MODE}, cursor::Cursor, cursor_shape::CursorShapeManagerState, decorator::DecorationRules, keyboard::KeyboardConfig, drawing_backend::{elements::Decorations, Backend}, CalloopData, window_manager::{send_configure_if_ready, Gaps, WindowManager}};
 
 pub struct State<B: Backend> {
     pub start_time: std::time::Instant,
     pub socket_name: OsString,
 
     pub space: Space<Window>,
-    pub wm: WindowManager<B>,
+    pub wm: WindowManager,
+    pub decorations: Decorations,
+    pub loop_handle: LoopHandle<'static, CalloopData<B>>,
     pub loop_signal: LoopSignal,
 
     pub backend_data: B,
@@ -40,12 +43,35 @@ pub struct State<B: Backend> {
     pub output_manager_state: OutputManagerState,
     pub seat_state: SeatState<Self>,
     pub data_device_state: DataDeviceState,
+    pub cursor_shape_manager_state: CursorShapeManagerState,
 
     pub seat: Seat<Self>,
+    pub pointer_location: Point<f64, Logical>,
+    // The surface with pointer focus and the serial of its wl_pointer.enter
+    pub pointer_enter: Option<(WlSurface, Serial)>,
+    pub cursor: Cursor,
+
+    pub bindings: Bindings,
+    // Keys held down right now, carried over when the keyboard is replaced
+    pub pressed_keys: Vec<u32>,
+    // Keys whose press ran a binding, their release is not sent to clients either
+    pub suppressed_keys: Vec<u32>,
+    // Buttons whose press went to a decoration, clients don't get their release
+    pub suppressed_buttons: Vec<u32>,
+
+    pub keyboard_config: KeyboardConfig,
+    // The index of the active layout among the configured ones
+    pub keyboard_layout: u32,
+    // The window with keyboard focus, as last told by the seat
+    pub keyboard_focus: Option<Window>,
+    // The window the active keyboard layout belongs to
+    pub layout_window: Option<Window>,
+    // Holds the current binding mode, rewritten on every change
+    pub mode_file: Option<PathBuf>,
 }
 
 impl<B: Backend> State<B> {
-    pub fn new(event_loop: &mut EventLoop<CalloopData<B>>, display: &mut Display<Self>) -> Self {
+    pub fn new(event_loop: &mut EventLoop<'static, CalloopData<B>>, display: &mut Display<Self>) -> Self {
         let start_time = std::time::Instant::now();
 
         let dh = display.handle();
@@ -58,10 +84,32 @@ impl<B: Backend> State<B> {
         let output_manager_state = OutputManagerState::new_with_xdg_output::<Self>(&dh);
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self>(&dh);
+        let cursor_shape_manager_state = CursorShapeManagerState::new::<Self>(&dh);
 
         let mut seat: Seat<Self> = seat_state.new_wl_seat(&dh, "x11");
 
-        seat.add_keyboard(Default::default(), 200, 200).unwrap();
+        let mut keyboard_config = KeyboardConfig::load();
+        if seat
+            .add_keyboard(
+                keyboard_config.xkb_config(0),
+                keyboard_config.repeat_delay,
+                keyboard_config.repeat_rate,
+            )
+            .is_err()
+        {
+            warn!("Could not load keymap {keyboard_config:?}, using the default");
+            keyboard_config = KeyboardConfig {
+                repeat_delay: keyboard_config.repeat_delay,
+                repeat_rate: keyboard_config.repeat_rate,
+                ..Default::default()
+            };
+            seat.add_keyboard(
+                Default::default(),
+                keyboard_config.repeat_delay,
+                keyboard_config.repeat_rate,
+            )
+            .unwrap();
+        }
 
         seat.add_pointer();
 
@@ -69,16 +117,46 @@ impl<B: Backend> State<B> {
 
         let socket_name = Self::init_wayland_listener(display, event_loop);
 
+        let loop_handle = event_loop.handle();
         let loop_signal = event_loop.get_signal();
 
         let backend_data = B::init(event_loop, display, &mut space);
 
+        // Comma separated layout names, in cycling order
+        let layouts = std::env::var("NEKOWM_LAYOUTS")
+            .map(|s| s.split(',').map(|name| name.trim().to_owned()).collect())
+            .unwrap_or_else(|_| vec![]);
+        let mut wm = WindowManager::new(backend_data.output().clone(), &layouts);
+        wm.set_decoration_rules(DecorationRules::from_env());
+
         std::env::set_var("WAYLAND_DISPLAY", &socket_name);
 
-        Self {
+        let mut bindings = Bindings::default();
+        if let Ok(timeout) = std::env::var("NEKOWM_CHORD_TIMEOUT_MS") {
+            match timeout.parse() {
+                Ok(millis) => bindings.set_chord_timeout(Duration::from_millis(millis)),
+                Err(_) => warn!("Invalid NEKOWM_CHORD_TIMEOUT_MS {timeout:?}"),
+            }
+        }
+
+        // Bars find the binding mode through NEKOWM_MODE_FILE
+        let mode_file = std::env::var_os("XDG_RUNTIME_DIR").map(|dir| {
+            let mut name = OsString::from("nekowm-");
+            name.push(&socket_name);
+            name.push(".mode");
+            PathBuf::from(dir).join(name)
+        });
+        if let Some(path) = &mode_file {
+            std::env::set_var("NEKOWM_MODE_FILE", path);
+        }
+
+        let state = Self {
             start_time,
             socket_name,
             space,
+            wm,
+            decorations: Decorations::default(),
+            loop_handle,
             loop_signal,
             backend_data,
             compositor_state,
@@ -89,7 +167,220 @@ impl<B: Backend> State<B> {
             output_manager_state,
             seat_state,
             data_device_state,
+            cursor_shape_manager_state,
             seat,
+            pointer_location: Point::from((0.0, 0.0)),
+            pointer_enter: None,
+            cursor: Cursor::new(),
+            bindings,
+            pressed_keys: vec![],
+            suppressed_keys: vec![],
+            suppressed_buttons: vec![],
+            keyboard_config,
+            keyboard_layout: 0,
+            keyboard_focus: None,
+            layout_window: None,
+            mode_file,
+        };
+        state.export_mode();
+        state
+    }
+
+    pub fn mapped_window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.space
+            .elements()
+            .find(|w| w.toplevel().wl_surface() == surface)
+            .cloned()
+    }
+
+    pub fn focused_window(&self) -> Option<Window> {
+        let focus = self.seat.get_keyboard()?.current_focus()?;
+        self.mapped_window_for_surface(&focus)
+    }
+
+    pub fn set_focus(&mut self, window: Option<Window>) {
+        let previous = self.focused_window();
+        if let Some(window) = &window {
+            self.space.raise_element(window, true);
+            self.wm.restack(&mut self.space);
+        }
+        // Raising changes who is activated, clients only see that in a configure
+        for window in previous.iter().chain(&window) {
+            let toplevel = window.toplevel();
+            let current_state = toplevel.current_state();
+            if toplevel.with_pending_state(|state| *state != current_state) {
+                send_configure_if_ready(toplevel);
+            }
+        }
+        let serial = SERIAL_COUNTER.next_serial();
+        self.seat.get_keyboard().unwrap().set_focus(
+            self,
+            window.map(|w| w.toplevel().wl_surface().clone()),
+            serial,
+        );
+        self.keyboard_focus_changed();
+    }
+
+    pub fn switch_workspace(&mut self, index: usize) {
+        if self.wm.switch_workspace(index, &mut self.space) {
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
+        }
+    }
+
+    pub fn move_focused_to_workspace(&mut self, index: usize) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        if self.wm.move_to_workspace(&window, index, &mut self.space) {
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
+        }
+    }
+
+    pub fn toggle_view(&mut self, index: usize) {
+        if self.wm.toggle_view(index, &mut self.space) {
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
+        }
+    }
+
+    pub fn toggle_focused_tag(&mut self, index: usize) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        if self.wm.toggle_tag(&window, index, &mut self.space) {
+            let focus = self.wm.focus_candidate();
+            self.set_focus(focus);
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        debug!("Running {action:?}");
+        let focused = self.focused_window();
+        match action {
+            Action::Spawn(command) => {
+                if let Err(err) = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .spawn()
+                {
+                    warn!("Could not spawn {command:?}: {err}");
+                }
+            }
+            Action::Close => {
+                if let Some(window) = focused {
+                    window.toplevel().send_close();
+                }
+            }
+            Action::FocusNext | Action::FocusPrevious => {
+                let window = self.wm.focus_step(action == Action::FocusNext);
+                if window.is_some() {
+                    self.set_focus(window);
+                }
+            }
+            Action::CycleLayout => self.wm.cycle_layout(&mut self.space),
+            Action::SelectLayout(name) => {
+                if !self.wm.select_layout(&name, &mut self.space) {
+                    warn!("No layout named {name}");
+                }
+            }
+            Action::Layout(message) => {
+                self.wm.send_layout_message(message, &mut self.space);
+            }
+            Action::AdjustGaps(delta) => {
+                let gaps = self.wm.gaps();
+                let gaps = Gaps {
+                    inner: gaps.inner + delta,
+                    outer: gaps.outer + delta,
+                    ..gaps
+                };
+                self.wm.set_gaps(gaps, &mut self.space);
+            }
+            Action::ToggleSmartGaps => {
+                let gaps = self.wm.gaps();
+                let gaps = Gaps {
+                    smart: !gaps.smart,
+                    ..gaps
+                };
+                self.wm.set_gaps(gaps, &mut self.space);
+            }
+            Action::ToggleFloating => {
+                if let Some(window) = focused {
+                    self.wm.toggle_floating(&window, &mut self.space);
+                }
+            }
+            Action::ToggleMaximized => {
+                if let Some(window) = focused {
+                    self.wm.toggle_maximized(&window, &mut self.space);
+                }
+            }
+            Action::Minimize => {
+                if let Some(window) = focused {
+                    self.wm.minimize(&window, &mut self.space);
+                    let focus = self.wm.focus_candidate();
+                    self.set_focus(focus);
+                }
+            }
+            Action::RestoreMinimized => {
+                if let Some(window) = self.wm.restore_minimized(&mut self.space) {
+                    self.set_focus(Some(window));
+                }
+            }
+            Action::SwitchWorkspace(index) => self.switch_workspace(index),
+            Action::MoveToWorkspace(index) => self.move_focused_to_workspace(index),
+            Action::ToggleView(index) => self.toggle_view(index),
+            Action::ToggleTag(index) => self.toggle_focused_tag(index),
+            Action::EnterMode(mode) => {
+                if self.bindings.set_mode(&mode) {
+                    debug!("Entered binding mode {mode}");
+                    self.export_mode();
+                } else {
+                    warn!("No binding mode named {mode}");
+                }
+            }
+            Action::ExitMode => {
+                self.bindings.set_mode(DEFAULT_MODE);
+                debug!("Entered binding mode {DEFAULT_MODE}");
+                self.export_mode();
+            }
+            Action::ReloadKeyboardConfig => {
+                self.set_keyboard_config(KeyboardConfig::load());
+            }
+            Action::NextKeyboardLayout => self.cycle_keyboard_layout(true),
+            Action::PreviousKeyboardLayout => self.cycle_keyboard_layout(false),
+            Action::Quit => self.loop_signal.stop(),
+        }
+    }
+
+    fn export_mode(&self) {
+        let Some(path) = &self.mode_file else {
+            return;
+        };
+        if let Err(err) = std::fs::write(path, format!("{}\n", self.bindings.mode())) {
+            warn!("Could not write the binding mode to {path:?}: {err}");
+        }
+    }
+
+    // Bars would otherwise show the mode of a compositor that is gone
+    pub fn remove_mode_file(&self) {
+        let Some(path) = &self.mode_file else {
+            return;
+        };
+        if let Err(err) = std::fs::remove_file(path) {
+            warn!("Could not remove the binding mode file {path:?}: {err}");
+        }
+    }
+
+    // A started sequence is dropped once its time is up, not only on the next key
+    pub(crate) fn start_chord_timer(&self) {
+        let timer = Timer::from_duration(self.bindings.chord_timeout());
+        let inserted = self.loop_handle.insert_source(timer, |_, _, data| {
+            data.state.bindings.expire(Instant::now());
+            TimeoutAction::Drop
+        });
+        if inserted.is_err() {
+            warn!("Could not start the key sequence timer");
         }
     }
 
@@ -103,7 +394,7 @@ impl<B: Backend> State<B> {
 
         handle
             .insert_source(socket, |client_stream, _, state| {
-                println!("Client connected!");
+                debug!("Client connected");
                 state
                     .display
                     .handle()
@@ -1,97 +1,1682 @@
+use std::{collections::HashMap, hash::Hash};
+
 use smithay::{
     desktop::{Space, Window},
     output::Output,
     reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
-    utils::{Logical, Size}, wayland::shell::xdg::{ToplevelState, ToplevelStateSet, ToplevelSurface}, input::Seat,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle, Size}, wayland::{shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData}, compositor::with_states},
 };
+use tracing::{debug, warn};
 
-use crate::{drawing_backend::Backend, state::State};
+use crate::decorator::{DecorationRules, DecorationState, DecorationStyle};
 
-pub struct WindowManager<B: Backend> {
-    output: Output,
-    active_layout: Box<dyn Layout<B>>,
+// Workspaces double as dwm style tags. Every window carries a bitmask of the
+// workspaces it is on, and any combination of them can be viewed at once.
+// Viewing several uses the layout and focus of the lowest one.
+pub struct Workspace {
+    name: String,
+    // Every layout keeps its own state, including while it is not active
+    layouts: Vec<Box<dyn Layout<Window>>>,
+    active_layout: usize,
+    focused: Option<Window>,
 }
 
-impl<B: Backend> WindowManager<B> {
-    pub fn new(output: Output) -> Self {
+impl Workspace {
+    // Every name must be known to `layout_by_name`
+    pub fn new(name: impl Into<String>, layouts: &[String]) -> Self {
         Self {
-            output,
-            active_layout: Box::new(MonocleLayout {}),
+            name: name.into(),
+            layouts: layouts
+                .iter()
+                .filter_map(|name| layout_by_name(name))
+                .collect(),
+            active_layout: 0,
+            focused: None,
         }
     }
 
-    fn layout(&mut self, space: &Space<Window>) {
-        self.active_layout.layout(&self.output, space);
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    fn lost_focus(&mut self, state: &Seat<State<B>>, space: &Space<Window>) {
-        self.active_layout.lost_focus(state, space);
+    pub fn active_layout(&self) -> &dyn Layout<Window> {
+        self.layouts[self.active_layout].as_ref()
     }
 }
 
-pub trait Layout<B: Backend> {
-    fn layout(&mut self, output: &Output, space: &Space<Window>);
-    fn lost_focus(&mut self, seat: &Seat<State<B>>, space: &Space<Window>) -> Option<ToplevelSurface>;
+pub const MAX_WORKSPACES: usize = u32::BITS as usize;
+
+pub const DEFAULT_LAYOUTS: [&str; 5] = ["tile", "dwindle", "scroll", "monocle", "floating"];
+
+// Every layout a workspace can be given, by name
+pub fn layout_by_name(name: &str) -> Option<Box<dyn Layout<Window>>> {
+    let layout: Box<dyn Layout<Window>> = match name {
+        "tile" => Box::new(MasterStackLayout::default()),
+        "dwindle" => Box::new(DwindleLayout::default()),
+        "scroll" => Box::new(ScrollingLayout::default()),
+        "monocle" => Box::new(MonocleLayout::default()),
+        "floating" => Box::new(FloatingLayout::default()),
+        _ => return None,
+    };
+    Some(layout)
 }
 
-pub struct MonocleLayout {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gaps {
+    // Between tiled windows
+    pub inner: i32,
+    // Around the edge of the output, on top of half the inner gap
+    pub outer: i32,
+    // No gaps at all while a single tiled window is shown
+    pub smart: bool,
+}
 
-impl<B: Backend> Layout<B> for MonocleLayout {
-    fn layout(&mut self, output: &Output, space: &Space<Window>) {
-        if space.elements().next().is_none() {
-            return;
+impl Default for Gaps {
+    fn default() -> Self {
+        Self {
+            inner: 8,
+            outer: 8,
+            smart: true,
         }
+    }
+}
+
+pub struct WindowManager {
+    output: Output,
+    // What every workspace is given, in cycling order
+    layouts: Vec<String>,
+    workspaces: Vec<Workspace>,
+    // Bitmask of the workspaces currently shown
+    view: u32,
+    // Tiled windows, in the order they were created
+    windows: Vec<Window>,
+    // Windows floating on top of the layout, whatever layout is active
+    floating: Vec<Window>,
+    tags: HashMap<Window, u32>,
+    // Last floating geometry, also restored when a window is floated again
+    floating_geometry: HashMap<Window, Rectangle<i32, Logical>>,
+    // Cover the whole output, whether they are tiled or floating
+    maximized: Vec<Window>,
+    // Hidden until restored, most recently minimized last
+    minimized: Vec<Window>,
+    gaps: Gaps,
+    decoration_style: DecorationStyle,
+    decoration_rules: DecorationRules,
+}
 
-        let active = space
-            .elements()
-            .find(|w| {
-                w.toplevel()
-                    .current_state()
-                    .states
-                    .contains(xdg_toplevel::State::Activated)
+impl WindowManager {
+    // Unknown layout names are skipped, and without any known ones every
+    // workspace gets the default layouts
+    pub fn new(output: Output, layouts: &[String]) -> Self {
+        let mut layouts = layouts
+            .iter()
+            .filter(|name| {
+                let known = layout_by_name(name).is_some();
+                if !known {
+                    warn!("No layout named {name}");
+                }
+                known
             })
-            .cloned();
+            .cloned()
+            .collect::<Vec<_>>();
+        if layouts.is_empty() {
+            layouts = DEFAULT_LAYOUTS.map(String::from).to_vec();
+        }
+
+        Self {
+            output,
+            workspaces: (1..=9)
+                .map(|i| Workspace::new(i.to_string(), &layouts))
+                .collect(),
+            layouts,
+            view: 1,
+            windows: vec![],
+            floating: vec![],
+            tags: HashMap::new(),
+            floating_geometry: HashMap::new(),
+            maximized: vec![],
+            minimized: vec![],
+            gaps: Gaps::default(),
+            decoration_style: DecorationStyle::default(),
+            decoration_rules: DecorationRules::default(),
+        }
+    }
+
+    pub fn gaps(&self) -> Gaps {
+        self.gaps
+    }
+
+    pub fn set_gaps(&mut self, gaps: Gaps, space: &mut Space<Window>) {
+        self.gaps = Gaps {
+            inner: gaps.inner.max(0),
+            outer: gaps.outer.max(0),
+            smart: gaps.smart,
+        };
+        self.layout(space);
+    }
+
+    pub fn decoration_style(&self) -> &DecorationStyle {
+        &self.decoration_style
+    }
+
+    pub fn set_decoration_style(&mut self, style: DecorationStyle, space: &mut Space<Window>) {
+        self.decoration_style = style;
+        self.layout(space);
+    }
+
+    pub fn decoration_rules(&self) -> &DecorationRules {
+        &self.decoration_rules
+    }
+
+    // Applies to decorations negotiated from now on
+    pub fn set_decoration_rules(&mut self, rules: DecorationRules) {
+        self.decoration_rules = rules;
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    // The workspace whose layout and focus are used for the current view
+    pub fn active_workspace(&self) -> usize {
+        self.view.trailing_zeros() as usize
+    }
+
+    pub fn view(&self) -> u32 {
+        self.view
+    }
+
+    pub fn tags(&self, window: &Window) -> u32 {
+        self.tags.get(window).copied().unwrap_or(0)
+    }
+
+    pub fn workspace_by_name(&self, name: &str) -> Option<usize> {
+        self.workspaces.iter().position(|w| w.name == name)
+    }
+
+    pub fn add_workspace(&mut self, name: impl Into<String>) -> Option<usize> {
+        if self.workspaces.len() >= MAX_WORKSPACES {
+            return None;
+        }
+        self.workspaces.push(Workspace::new(name, &self.layouts));
+        Some(self.workspaces.len() - 1)
+    }
+
+    fn workspace(&self) -> &Workspace {
+        &self.workspaces[self.active_workspace()]
+    }
+
+    fn workspace_mut(&mut self) -> &mut Workspace {
+        let index = self.active_workspace();
+        &mut self.workspaces[index]
+    }
+
+    pub fn is_visible(&self, window: &Window) -> bool {
+        self.tags(window) & self.view != 0 && !self.minimized.contains(window)
+    }
+
+    fn visible<'a>(&'a self, windows: &'a [Window]) -> impl Iterator<Item = &'a Window> + 'a {
+        windows.iter().filter(|w| self.is_visible(w))
+    }
+
+    // Finds a window whether it is shown or not, hidden ones are not in the space
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.windows
+            .iter()
+            .chain(self.floating.iter())
+            .find(|w| w.toplevel().wl_surface() == surface)
+            .cloned()
+    }
+
+    pub fn active_layout(&self) -> &dyn Layout<Window> {
+        self.workspace().active_layout()
+    }
+
+    pub fn layout_names(&self) -> impl Iterator<Item = &str> {
+        self.workspace().layouts.iter().map(|l| l.name())
+    }
+
+    pub fn cycle_layout(&mut self, space: &mut Space<Window>) {
+        let workspace = self.workspace_mut();
+        workspace.active_layout = (workspace.active_layout + 1) % workspace.layouts.len();
+        debug!("Switched to layout {}", self.active_layout().name());
+        self.layout(space);
+    }
+
+    pub fn select_layout(&mut self, name: &str, space: &mut Space<Window>) -> bool {
+        let workspace = self.workspace_mut();
+        let Some(index) = workspace.layouts.iter().position(|l| l.name() == name) else {
+            return false;
+        };
+        workspace.active_layout = index;
+        debug!("Switched to layout {name}");
+        self.layout(space);
+        true
+    }
+
+    // Passes the message to the active layout, and relayouts if it took it
+    pub fn send_layout_message(
+        &mut self,
+        message: LayoutMessage,
+        space: &mut Space<Window>,
+    ) -> bool {
+        let focused = self.workspace().focused.clone();
+        let workspace = self.workspace_mut();
+        let layout = &mut workspace.layouts[workspace.active_layout];
+        let handled = layout.message(message, focused.as_ref());
+        if handled {
+            self.layout(space);
+        }
+        handled
+    }
+
+    pub fn layout(&mut self, space: &mut Space<Window>) {
+        let area = output_area(&self.output, space);
+        let windows = self.visible(&self.windows).cloned().collect::<Vec<_>>();
+        let focused = self.workspace().focused.clone();
+        let gaps = self.gaps;
+        let style = self.decoration_style.clone();
+        let workspace = self.workspace_mut();
+        let layout = &mut workspace.layouts[workspace.active_layout];
+        let tiled = !layout.is_floating();
+
+        // Layouts don't know about gaps, the outer gap shrinks the area they get
+        // and the inner gap shrinks every window they place
+        let mut plan = if tiled {
+            layout.layout(&windows, focused.as_ref(), shrink(area, gaps.outer))
+        } else {
+            layout.layout(&windows, focused.as_ref(), area)
+        };
+        if tiled {
+            if gaps.smart && plan.len() == 1 {
+                plan[0].geometry = area;
+            } else {
+                for placement in &mut plan {
+                    placement.geometry = shrink(placement.geometry, gaps.inner / 2);
+                }
+            }
+            // Tiled placements include the frame, the client gets what is inside it
+            for placement in &mut plan {
+                if DecorationState::is_server(placement.window.toplevel().wl_surface()) {
+                    placement.geometry = style.client_geometry(placement.geometry);
+                }
+            }
+        }
+        for placement in &mut plan {
+            if self.maximized.contains(&placement.window) {
+                *placement = self.maximized_placement(&placement.window, area);
+            }
+        }
+
+        // Windows the layout leaves out are hidden, as are the ones not in view
+        for window in &self.windows {
+            if !plan.iter().any(|p| p.window == *window) {
+                space.unmap_elem(window);
+            }
+        }
+        for placement in plan {
+            apply(space, placement);
+        }
+
+        for window in &self.floating {
+            if !self.is_visible(window) {
+                space.unmap_elem(window);
+            } else if self.maximized.contains(window) {
+                apply(space, self.maximized_placement(window, area));
+            } else if let Some(geometry) = self.floating_geometry.get(window) {
+                space.map_element(window.clone(), geometry.loc, false);
+            } else {
+                space.unmap_elem(window);
+            }
+        }
+
+        self.restack(space);
+    }
+
+    fn maximized_placement(
+        &self,
+        window: &Window,
+        area: Rectangle<i32, Logical>,
+    ) -> Placement<Window> {
+        let geometry = if DecorationState::is_server(window.toplevel().wl_surface()) {
+            self.decoration_style.client_geometry(area)
+        } else {
+            area
+        };
+        Placement {
+            window: window.clone(),
+            geometry,
+            states: vec![xdg_toplevel::State::Maximized],
+        }
+    }
+
+    // Keeps floating windows above the tiled ones, and maximized ones above both
+    pub fn restack(&self, space: &mut Space<Window>) {
+        for window in self.visible(&self.floating) {
+            space.raise_element(window, false);
+        }
+        for window in self.visible(&self.maximized) {
+            space.raise_element(window, false);
+        }
+    }
+
+    pub fn view_tags(&mut self, view: u32, space: &mut Space<Window>) -> bool {
+        let view = view & self.all_tags();
+        if view == 0 || view == self.view {
+            return false;
+        }
+        self.view = view;
+        debug!("Viewing tags {view:#b}");
+        self.layout(space);
+        true
+    }
+
+    pub fn switch_workspace(&mut self, index: usize, space: &mut Space<Window>) -> bool {
+        index < self.workspaces.len() && self.view_tags(1 << index, space)
+    }
+
+    // Shows or hides a workspace alongside the ones already in view
+    pub fn toggle_view(&mut self, index: usize, space: &mut Space<Window>) -> bool {
+        index < self.workspaces.len() && self.view_tags(self.view ^ (1 << index), space)
+    }
+
+    pub fn set_tags(&mut self, window: &Window, tags: u32, space: &mut Space<Window>) -> bool {
+        let tags = tags & self.all_tags();
+        let Some(current) = self.tags.get_mut(window) else {
+            return false;
+        };
+        if tags == 0 || tags == *current {
+            return false;
+        }
+        *current = tags;
 
-        let active = if let Some(active) = active {
-            active
+        // It is focused on the workspaces it was sent to
+        for (i, workspace) in self.workspaces.iter_mut().enumerate() {
+            if tags & (1 << i) != 0 {
+                workspace.focused = Some(window.clone());
+            }
+        }
+        self.layout(space);
+        true
+    }
+
+    pub fn move_to_workspace(
+        &mut self,
+        window: &Window,
+        index: usize,
+        space: &mut Space<Window>,
+    ) -> bool {
+        index < self.workspaces.len() && self.set_tags(window, 1 << index, space)
+    }
+
+    // Adds the window to or removes it from a workspace, keeping it on at least one
+    pub fn toggle_tag(&mut self, window: &Window, index: usize, space: &mut Space<Window>) -> bool {
+        index < self.workspaces.len() && self.set_tags(window, self.tags(window) ^ (1 << index), space)
+    }
+
+    fn all_tags(&self) -> u32 {
+        if self.workspaces.len() >= MAX_WORKSPACES {
+            u32::MAX
         } else {
-            space.elements().last().unwrap().clone()
+            (1 << self.workspaces.len()) - 1
+        }
+    }
+
+    // The window that should have focus in the current view
+    pub fn focus_candidate(&self) -> Option<Window> {
+        self.workspace()
+            .focused
+            .clone()
+            .filter(|w| self.is_visible(w))
+            .or_else(|| self.visible(&self.floating).last().cloned())
+            .or_else(|| self.visible(&self.windows).last().cloned())
+    }
+
+    // The window after or before the focused one, tiled windows come first
+    pub fn focus_step(&self, forward: bool) -> Option<Window> {
+        let windows = self
+            .visible(&self.windows)
+            .chain(self.visible(&self.floating))
+            .collect::<Vec<_>>();
+        if windows.is_empty() {
+            return None;
+        }
+        let focused = self.workspace().focused.as_ref();
+        let index = match windows.iter().position(|w| Some(*w) == focused) {
+            Some(i) if forward => (i + 1) % windows.len(),
+            Some(i) => (i + windows.len() - 1) % windows.len(),
+            None => 0,
         };
+        Some(windows[index].clone())
+    }
+
+    // True if the active layout places windows differently now
+    pub fn focus_changed(&mut self, window: &Window) -> bool {
+        if !self.is_visible(window) || self.workspace().focused.as_ref() == Some(window) {
+            return false;
+        }
+        let workspace = self.workspace_mut();
+        workspace.focused = Some(window.clone());
+        workspace.layouts[workspace.active_layout].focus_changed(window)
+    }
+
+    // Layouts keep hidden windows in place, so they are told when one is gone for good
+    fn forget(&mut self, window: &Window) {
+        for workspace in &mut self.workspaces {
+            for layout in &mut workspace.layouts {
+                layout.remove(window);
+            }
+        }
+    }
+
+    pub fn toplevel_created(&mut self, window: &Window, space: &mut Space<Window>) {
+        // New windows go on every workspace in view
+        self.tags.insert(window.clone(), self.view);
+
+        // Dialogs float by default
+        if window.toplevel().parent().is_some() {
+            self.float(window, space);
+        } else {
+            self.windows.push(window.clone());
+        }
+        // New windows get keyboard focus before they are managed here
+        self.workspace_mut().focused = Some(window.clone());
+        self.layout(space);
+    }
 
-        let toplevel = active.toplevel();
+    pub fn toplevel_destroyed(&mut self, window: &Window, space: &mut Space<Window>) {
+        self.forget(window);
+        self.windows.retain(|w| w != window);
+        self.floating.retain(|w| w != window);
+        self.tags.remove(window);
+        self.floating_geometry.remove(window);
+        self.maximized.retain(|w| w != window);
+        self.minimized.retain(|w| w != window);
+        for workspace in &mut self.workspaces {
+            if workspace.focused.as_ref() == Some(window) {
+                workspace.focused = None;
+            }
+        }
+        self.layout(space);
+    }
+
+    pub fn is_maximized(&self, window: &Window) -> bool {
+        self.maximized.contains(window)
+    }
+
+    pub fn set_maximized(&mut self, window: &Window, maximized: bool, space: &mut Space<Window>) {
+        if maximized == self.is_maximized(window) {
+            return;
+        }
+        if maximized {
+            self.maximized.push(window.clone());
+        } else {
+            self.maximized.retain(|w| w != window);
+            // Floating windows get their old size back, tiled ones get one from the layout
+            if let Some(geometry) = self.floating_geometry.get(window).copied()
+                && self.floating.contains(window)
+            {
+                apply(
+                    space,
+                    Placement {
+                        window: window.clone(),
+                        geometry,
+                        states: vec![],
+                    },
+                );
+            }
+        }
+        self.layout(space);
+    }
+
+    pub fn toggle_maximized(&mut self, window: &Window, space: &mut Space<Window>) {
+        self.set_maximized(window, !self.is_maximized(window), space);
+    }
+
+    pub fn minimize(&mut self, window: &Window, space: &mut Space<Window>) {
+        if self.minimized.contains(window) || !self.tags.contains_key(window) {
+            return;
+        }
+        self.minimized.push(window.clone());
+        self.layout(space);
+    }
+
+    // Brings back the most recently minimized window on a workspace in view
+    pub fn restore_minimized(&mut self, space: &mut Space<Window>) -> Option<Window> {
+        let index = self
+            .minimized
+            .iter()
+            .rposition(|w| self.tags(w) & self.view != 0)?;
+        let window = self.minimized.remove(index);
+        self.workspace_mut().focused = Some(window.clone());
+        self.layout(space);
+        Some(window)
+    }
+
+    pub fn is_floating(&self, window: &Window) -> bool {
+        self.active_layout().is_floating() || self.floating.contains(window)
+    }
+
+    // Called once a move or resize grab is done with the window
+    pub fn window_moved(&mut self, window: &Window, geometry: Rectangle<i32, Logical>) {
+        if self.floating.contains(window) {
+            self.floating_geometry.insert(window.clone(), geometry);
+        } else {
+            let workspace = self.workspace_mut();
+            workspace.layouts[workspace.active_layout].window_moved(window, geometry);
+        }
+    }
+
+    pub fn toggle_floating(&mut self, window: &Window, space: &mut Space<Window>) {
+        if self.floating.contains(window) {
+            self.floating.retain(|w| w != window);
+            self.windows.push(window.clone());
+            if let Some(loc) = space.element_location(window) {
+                let geometry = Rectangle::from_loc_and_size(loc, window.geometry().size);
+                self.floating_geometry.insert(window.clone(), geometry);
+            }
+        } else if self.windows.contains(window) {
+            self.windows.retain(|w| w != window);
+            self.forget(window);
+            self.float(window, space);
+        }
+        self.layout(space);
+    }
+
+    // Floats a tiled window right where it is, so it can be dragged out of the layout
+    pub fn float_in_place(&mut self, window: &Window, space: &mut Space<Window>) {
+        if !self.windows.contains(window) {
+            return;
+        }
+        if let Some(loc) = space.element_location(window) {
+            let geometry = Rectangle::from_loc_and_size(loc, window.geometry().size);
+            self.floating_geometry.insert(window.clone(), geometry);
+        }
+        self.toggle_floating(window, space);
+    }
+
+    fn float(&mut self, window: &Window, space: &mut Space<Window>) {
+        let geometry = self.floating_geometry.get(window).copied().unwrap_or_else(|| {
+            // Centered, at half the size of the output
+            let area = output_area(&self.output, space);
+            let size = Size::from((area.size.w / 2, area.size.h / 2));
+            let loc = (area.loc.x + size.w / 2, area.loc.y + size.h / 2);
+            Rectangle::from_loc_and_size(loc, size)
+        });
+
+        self.floating_geometry.insert(window.clone(), geometry);
+        self.floating.push(window.clone());
+        apply(
+            space,
+            Placement {
+                window: window.clone(),
+                geometry,
+                states: vec![],
+            },
+        );
+    }
+}
+
+// Where a layout wants a window, windows without a placement are hidden
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement<W> {
+    pub window: W,
+    pub geometry: Rectangle<i32, Logical>,
+    pub states: Vec<xdg_toplevel::State>,
+}
+
+// Adjustments to a layout's own settings, layouts ignore the ones they don't have
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutMessage {
+    // Changes the number of master windows by this much
+    MasterCount(i32),
+    // Changes the share of the width the masters get by this much
+    MasterRatio(f32),
+    // Changes the width of columns, as a share of the output, by this much
+    ColumnWidth(f32),
+    // Moves the focused window into the column to its left
+    ConsumeIntoColumn,
+    // Moves the focused window out of its column into a new one
+    ExpelFromColumn,
+}
+
+// Layouts only compute placements, so they can work on any window handle and
+// don't need a running compositor. Windows that appear in `windows` for the
+// first time are new. Known windows missing from it are hidden for now, by
+// minimizing or tags, and keep their place until `remove` is called.
+pub trait Layout<W> {
+    fn name(&self) -> &str;
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>>;
+
+    // Floating layouts let clients move and resize their windows themselves
+    fn is_floating(&self) -> bool {
+        false
+    }
+
+    fn window_moved(&mut self, _window: &W, _geometry: Rectangle<i32, Logical>) {}
+
+    // The window is closed or no longer tiled
+    fn remove(&mut self, window: &W);
+
+    // True if placements depend on focus and have to be redone
+    fn focus_changed(&mut self, _window: &W) -> bool {
+        false
+    }
+
+    // True if the message changed anything
+    fn message(&mut self, _message: LayoutMessage, _focused: Option<&W>) -> bool {
+        false
+    }
+}
+
+// States set by layouts, everything else is left as the client or seat set it
+const LAYOUT_STATES: [xdg_toplevel::State; 5] = [
+    xdg_toplevel::State::Maximized,
+    xdg_toplevel::State::TiledLeft,
+    xdg_toplevel::State::TiledRight,
+    xdg_toplevel::State::TiledTop,
+    xdg_toplevel::State::TiledBottom,
+];
+
+const TILED: [xdg_toplevel::State; 4] = [
+    xdg_toplevel::State::TiledLeft,
+    xdg_toplevel::State::TiledRight,
+    xdg_toplevel::State::TiledTop,
+    xdg_toplevel::State::TiledBottom,
+];
+
+fn output_area(output: &Output, space: &Space<Window>) -> Rectangle<i32, Logical> {
+    space.output_geometry(output).unwrap_or_else(|| {
         let s = output.current_mode().unwrap().size;
-        let size = (s.w.into(), s.h.into()).into();
-        toplevel.with_pending_state(|state| state.size = Some(size));
+        Rectangle::from_loc_and_size((0, 0), (s.w, s.h))
+    })
+}
+
+fn shrink(rect: Rectangle<i32, Logical>, by: i32) -> Rectangle<i32, Logical> {
+    Rectangle::from_loc_and_size(
+        (rect.loc.x + by, rect.loc.y + by),
+        ((rect.size.w - 2 * by).max(1), (rect.size.h - 2 * by).max(1)),
+    )
+}
+
+// Configures sent before the client's initial commit would be a protocol error,
+// the compositor sends the pending state along with the initial configure instead
+pub fn send_configure_if_ready(toplevel: &ToplevelSurface) {
+    let initial_configure_sent = with_states(toplevel.wl_surface(), |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .initial_configure_sent
+    });
+
+    if initial_configure_sent {
         toplevel.send_configure();
-        for element in space
-            .elements()
-            .filter(|w| **w != active)
+    }
+}
+
+fn apply(space: &mut Space<Window>, placement: Placement<Window>) {
+    let toplevel = placement.window.toplevel();
+    toplevel.with_pending_state(|state| {
+        state.size = Some(placement.geometry.size);
+        for s in LAYOUT_STATES {
+            state.states.unset(s);
+        }
+        for s in &placement.states {
+            state.states.set(*s);
+        }
+    });
+    send_configure_if_ready(toplevel);
+    space.map_element(placement.window, placement.geometry.loc, false);
+}
+
+// The windows in `windows` that are not known yet
+fn new_windows<'a, W: PartialEq>(known: &'a [W], windows: &'a [W]) -> impl Iterator<Item = &'a W> {
+    windows.iter().filter(|w| !known.contains(w))
+}
+
+// Splits `length` into `count` parts whose sizes differ by at most one
+fn split(start: i32, length: i32, count: usize, index: usize) -> (i32, i32) {
+    let count = count as i32;
+    let index = index as i32;
+    let from = start + length * index / count;
+    let to = start + length * (index + 1) / count;
+    (from, to - from)
+}
+
+pub struct MonocleLayout<W> {
+    last: Option<W>,
+}
+
+impl<W> Default for MonocleLayout<W> {
+    fn default() -> Self {
+        Self { last: None }
+    }
+}
+
+impl<W: Clone + PartialEq> Layout<W> for MonocleLayout<W> {
+    fn name(&self) -> &str {
+        "monocle"
+    }
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>> {
+        // Show the focused window, or the most recent one if focus is elsewhere
+        let shown = focused
+            .filter(|w| windows.contains(w))
+            .or(self.last.as_ref().filter(|w| windows.contains(w)))
+            .or(windows.last())
+            .cloned();
+        self.last = shown.clone();
+
+        shown
+            .into_iter()
+            .map(|window| Placement {
+                window,
+                geometry: area,
+                states: TILED.to_vec(),
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, window: &W) {
+        if self.last.as_ref() == Some(window) {
+            self.last = None;
+        }
+    }
+
+    fn focus_changed(&mut self, window: &W) -> bool {
+        self.last.as_ref() != Some(window)
+    }
+}
+
+pub struct MasterStackLayout<W> {
+    windows: Vec<W>,
+    master_count: usize,
+    master_ratio: f32,
+}
+
+impl<W> MasterStackLayout<W> {
+    pub fn new(master_count: usize, master_ratio: f32) -> Self {
+        Self {
+            windows: vec![],
+            master_count,
+            master_ratio: master_ratio.clamp(0.05, 0.95),
+        }
+    }
+
+    pub fn master_count(&self) -> usize {
+        self.master_count
+    }
+
+    pub fn set_master_count(&mut self, master_count: usize) {
+        self.master_count = master_count;
+    }
+
+    pub fn master_ratio(&self) -> f32 {
+        self.master_ratio
+    }
+
+    pub fn set_master_ratio(&mut self, master_ratio: f32) {
+        self.master_ratio = master_ratio.clamp(0.05, 0.95);
+    }
+}
+
+impl<W> Default for MasterStackLayout<W> {
+    fn default() -> Self {
+        Self::new(1, 0.55)
+    }
+}
+
+impl<W: Clone + PartialEq> Layout<W> for MasterStackLayout<W> {
+    fn name(&self) -> &str {
+        "tile"
+    }
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        _focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>> {
+        // New windows become the master, pushing the old master onto the stack
+        for window in new_windows(&self.windows, windows)
             .cloned()
             .collect::<Vec<_>>()
         {
-            let toplevel = element.toplevel();
-            toplevel.with_pending_state(|state| {
-                state.size = Some((0, 0).into());
-                state.states = ToplevelStateSet::default();
+            self.windows.insert(0, window);
+        }
+
+        let shown = self
+            .windows
+            .iter()
+            .filter(|w| windows.contains(w))
+            .collect::<Vec<_>>();
+        if shown.is_empty() {
+            return vec![];
+        }
+
+        let masters = self.master_count.min(shown.len());
+        let stacked = shown.len() - masters;
+
+        let master_width = match (masters, stacked) {
+            (0, _) => 0,
+            (_, 0) => area.size.w,
+            _ => (area.size.w as f32 * self.master_ratio) as i32,
+        };
+
+        shown
+            .into_iter()
+            .enumerate()
+            .map(|(i, window)| {
+                let geometry = if i < masters {
+                    let (y, h) = split(area.loc.y, area.size.h, masters, i);
+                    Rectangle::from_loc_and_size((area.loc.x, y), (master_width, h))
+                } else {
+                    let (y, h) = split(area.loc.y, area.size.h, stacked, i - masters);
+                    Rectangle::from_loc_and_size(
+                        (area.loc.x + master_width, y),
+                        (area.size.w - master_width, h),
+                    )
+                };
+                Placement {
+                    window: window.clone(),
+                    geometry,
+                    states: TILED.to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, window: &W) {
+        self.windows.retain(|w| w != window);
+    }
+
+    fn message(&mut self, message: LayoutMessage, _focused: Option<&W>) -> bool {
+        match message {
+            LayoutMessage::MasterCount(delta) => {
+                let count = (self.master_count() as i32 + delta).max(0) as usize;
+                let changed = count != self.master_count();
+                self.set_master_count(count);
+                changed
+            }
+            LayoutMessage::MasterRatio(delta) => {
+                let ratio = self.master_ratio();
+                self.set_master_ratio(ratio + delta);
+                ratio != self.master_ratio()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum BspNode<W> {
+    Leaf(W),
+    Split {
+        ratio: f32,
+        first: Box<BspNode<W>>,
+        second: Box<BspNode<W>>,
+    },
+}
+
+impl<W: Clone + PartialEq> BspNode<W> {
+    fn contains(&self, window: &W) -> bool {
+        match self {
+            BspNode::Leaf(w) => w == window,
+            BspNode::Split { first, second, .. } => {
+                first.contains(window) || second.contains(window)
+            }
+        }
+    }
+
+    fn leaves(&self, out: &mut Vec<W>) {
+        match self {
+            BspNode::Leaf(w) => out.push(w.clone()),
+            BspNode::Split { first, second, .. } => {
+                first.leaves(out);
+                second.leaves(out);
+            }
+        }
+    }
+
+    fn last_leaf(&self) -> &W {
+        match self {
+            BspNode::Leaf(w) => w,
+            BspNode::Split { second, .. } => second.last_leaf(),
+        }
+    }
+
+    // Replaces the leaf holding `target` with a split of it and `window`
+    fn split(self, target: &W, window: W) -> BspNode<W> {
+        match self {
+            BspNode::Leaf(w) if w == *target => BspNode::Split {
+                ratio: 0.5,
+                first: Box::new(BspNode::Leaf(w)),
+                second: Box::new(BspNode::Leaf(window)),
+            },
+            BspNode::Split {
+                ratio,
+                first,
+                second,
+            } => {
+                if first.contains(target) {
+                    BspNode::Split {
+                        ratio,
+                        first: Box::new(first.split(target, window)),
+                        second,
+                    }
+                } else {
+                    BspNode::Split {
+                        ratio,
+                        first,
+                        second: Box::new(second.split(target, window)),
+                    }
+                }
+            }
+            leaf => leaf,
+        }
+    }
+
+    // Drops the leaves whose window isn't kept, a sibling takes the place of
+    // its parent split
+    fn retain(self, keep: &dyn Fn(&W) -> bool) -> Option<BspNode<W>> {
+        match self {
+            BspNode::Leaf(w) if !keep(&w) => None,
+            BspNode::Split {
+                ratio,
+                first,
+                second,
+            } => match (first.retain(keep), second.retain(keep)) {
+                (Some(first), Some(second)) => Some(BspNode::Split {
+                    ratio,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+            leaf => Some(leaf),
+        }
+    }
+
+    fn layout(&self, area: Rectangle<i32, Logical>, plan: &mut Vec<Placement<W>>) {
+        match self {
+            BspNode::Leaf(window) => plan.push(Placement {
+                window: window.clone(),
+                geometry: area,
+                states: TILED.to_vec(),
+            }),
+            BspNode::Split {
+                ratio,
+                first,
+                second,
+            } => {
+                // Dwindle: split along the longer side of the area
+                let (first_area, second_area) = if area.size.w >= area.size.h {
+                    let w = (area.size.w as f32 * ratio) as i32;
+                    (
+                        Rectangle::from_loc_and_size(area.loc, (w, area.size.h)),
+                        Rectangle::from_loc_and_size(
+                            (area.loc.x + w, area.loc.y),
+                            (area.size.w - w, area.size.h),
+                        ),
+                    )
+                } else {
+                    let h = (area.size.h as f32 * ratio) as i32;
+                    (
+                        Rectangle::from_loc_and_size(area.loc, (area.size.w, h)),
+                        Rectangle::from_loc_and_size(
+                            (area.loc.x, area.loc.y + h),
+                            (area.size.w, area.size.h - h),
+                        ),
+                    )
+                };
+                first.layout(first_area, plan);
+                second.layout(second_area, plan);
+            }
+        }
+    }
+}
+
+pub struct DwindleLayout<W> {
+    root: Option<BspNode<W>>,
+    // Focus as of the last layout, new windows split this node
+    focused: Option<W>,
+}
+
+impl<W> Default for DwindleLayout<W> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            focused: None,
+        }
+    }
+}
+
+impl<W: Clone + PartialEq> Layout<W> for DwindleLayout<W> {
+    fn name(&self) -> &str {
+        "dwindle"
+    }
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>> {
+        let mut known = vec![];
+        if let Some(root) = &self.root {
+            root.leaves(&mut known);
+        }
+
+        for window in new_windows(&known, windows) {
+            self.root = Some(match self.root.take() {
+                None => BspNode::Leaf(window.clone()),
+                Some(root) => {
+                    let target = self
+                        .focused
+                        .clone()
+                        .filter(|w| root.contains(w))
+                        .unwrap_or_else(|| root.last_leaf().clone());
+                    root.split(&target, window.clone())
+                }
             });
-            space.map_element(element, (-1, -1), false);
+            self.focused = Some(window.clone());
         }
 
-        let toplevel = active.toplevel();
-        toplevel.with_pending_state(|state| {
-            state.size = Some((0, 0).into());
-            state.states = ToplevelStateSet::default();
-        });
-        space.map_element(active, (0, 0), true);
+        if let Some(focused) = focused.filter(|w| windows.contains(w)) {
+            self.focused = Some(focused.clone());
+        }
+
+        // Hidden windows give up their space until they are shown again
+        let mut plan = vec![];
+        let shown = self
+            .root
+            .clone()
+            .and_then(|root| root.retain(&|w| windows.contains(w)));
+        if let Some(root) = shown {
+            root.layout(area, &mut plan);
+        }
+        plan
     }
 
-    fn lost_focus(&mut self, seat: &Seat<State<B>>, space: &Space<Window>) -> Option<ToplevelSurface> {
-        if space.elements().next().is_none() {
-            return None;
+    fn remove(&mut self, window: &W) {
+        self.root = self
+            .root
+            .take()
+            .and_then(|root| root.retain(&|w| w != window));
+        if self.focused.as_ref() == Some(window) {
+            self.focused = None;
+        }
+    }
+
+    fn focus_changed(&mut self, window: &W) -> bool {
+        self.focused = Some(window.clone());
+        false
+    }
+}
+
+pub struct ScrollingLayout<W> {
+    columns: Vec<Vec<W>>,
+    column_width: f32,
+    scroll_offset: i32,
+    // Focus as of the last layout, new windows open right of this column
+    focused: Option<W>,
+}
+
+impl<W: Clone + PartialEq> ScrollingLayout<W> {
+    pub fn new(column_width: f32) -> Self {
+        Self {
+            columns: vec![],
+            column_width: column_width.clamp(0.1, 1.0),
+            scroll_offset: 0,
+            focused: None,
+        }
+    }
+
+    pub fn column_width(&self) -> f32 {
+        self.column_width
+    }
+
+    pub fn set_column_width(&mut self, column_width: f32) {
+        self.column_width = column_width.clamp(0.1, 1.0);
+    }
+
+    pub fn scroll_offset(&self) -> i32 {
+        self.scroll_offset
+    }
+
+    fn column_of(&self, window: &W) -> Option<usize> {
+        self.columns.iter().position(|c| c.contains(window))
+    }
+
+    // Moves the window to the bottom of the column to its left
+    pub fn consume_into_previous_column(&mut self, window: &W) -> bool {
+        let Some(index) = self.column_of(window) else {
+            return false;
+        };
+        if index == 0 {
+            return false;
+        }
+        self.columns[index].retain(|w| w != window);
+        self.columns[index - 1].push(window.clone());
+        if self.columns[index].is_empty() {
+            self.columns.remove(index);
+        }
+        true
+    }
+
+    // Moves the window out of its column into a new column to the right
+    pub fn expel_from_column(&mut self, window: &W) -> bool {
+        let Some(index) = self.column_of(window) else {
+            return false;
+        };
+        if self.columns[index].len() == 1 {
+            return false;
+        }
+        self.columns[index].retain(|w| w != window);
+        self.columns.insert(index + 1, vec![window.clone()]);
+        true
+    }
+}
+
+impl<W: Clone + PartialEq> Default for ScrollingLayout<W> {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl<W: Clone + PartialEq> Layout<W> for ScrollingLayout<W> {
+    fn name(&self) -> &str {
+        "scroll"
+    }
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>> {
+        for window in windows {
+            if self.column_of(window).is_some() {
+                continue;
+            }
+            // New windows open in their own column right of the focused one
+            let index = self
+                .focused
+                .as_ref()
+                .and_then(|w| self.column_of(w))
+                .map_or(self.columns.len(), |i| i + 1);
+            self.columns.insert(index, vec![window.clone()]);
+            self.focused = Some(window.clone());
+        }
+
+        if let Some(focused) = focused.filter(|w| windows.contains(w)) {
+            self.focused = Some(focused.clone());
+        }
+
+        // Hidden windows give up their space until they are shown again
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| c.iter().filter(|w| windows.contains(w)).collect::<Vec<_>>())
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            self.scroll_offset = 0;
+            return vec![];
+        }
+
+        let width = (area.size.w as f32 * self.column_width) as i32;
+
+        // Scroll just enough to bring the focused column into view
+        if let Some(focused) = focused.and_then(|w| columns.iter().position(|c| c.contains(&w))) {
+            let x = focused as i32 * width;
+            if x < self.scroll_offset {
+                self.scroll_offset = x;
+            } else if x + width > self.scroll_offset + area.size.w {
+                self.scroll_offset = x + width - area.size.w;
+            }
+        }
+        let max_offset = (columns.len() as i32 * width - area.size.w).max(0);
+        self.scroll_offset = self.scroll_offset.clamp(0, max_offset);
+
+        let mut plan = vec![];
+        for (i, column) in columns.iter().enumerate() {
+            let x = area.loc.x + i as i32 * width - self.scroll_offset;
+            for (j, window) in column.iter().enumerate() {
+                let (y, h) = split(area.loc.y, area.size.h, column.len(), j);
+                plan.push(Placement {
+                    window: (*window).clone(),
+                    geometry: Rectangle::from_loc_and_size((x, y), (width, h)),
+                    states: TILED.to_vec(),
+                });
+            }
         }
+        plan
+    }
+
+    fn remove(&mut self, window: &W) {
+        for column in &mut self.columns {
+            column.retain(|w| w != window);
+        }
+        self.columns.retain(|c| !c.is_empty());
+        if self.focused.as_ref() == Some(window) {
+            self.focused = None;
+        }
+    }
+
+    // The view scrolls to whatever gets focus
+    fn focus_changed(&mut self, window: &W) -> bool {
+        self.focused = Some(window.clone());
+        true
+    }
+
+    fn message(&mut self, message: LayoutMessage, focused: Option<&W>) -> bool {
+        match (message, focused) {
+            (LayoutMessage::ColumnWidth(delta), _) => {
+                let width = self.column_width();
+                self.set_column_width(width + delta);
+                width != self.column_width()
+            }
+            (LayoutMessage::ConsumeIntoColumn, Some(window)) => {
+                self.consume_into_previous_column(window)
+            }
+            (LayoutMessage::ExpelFromColumn, Some(window)) => self.expel_from_column(window),
+            _ => false,
+        }
+    }
+}
+
+pub struct FloatingLayout<W> {
+    geometry: HashMap<W, Rectangle<i32, Logical>>,
+}
+
+impl<W> Default for FloatingLayout<W> {
+    fn default() -> Self {
+        Self {
+            geometry: HashMap::new(),
+        }
+    }
+}
+
+impl<W: Clone + Eq + Hash> Layout<W> for FloatingLayout<W> {
+    fn name(&self) -> &str {
+        "floating"
+    }
+
+    fn layout(
+        &mut self,
+        windows: &[W],
+        _focused: Option<&W>,
+        area: Rectangle<i32, Logical>,
+    ) -> Vec<Placement<W>> {
+        windows
+            .iter()
+            .enumerate()
+            .map(|(i, window)| {
+                // Windows keep wherever they were moved to, new ones cascade
+                // from the top left corner of the area
+                let geometry = *self.geometry.entry(window.clone()).or_insert_with(|| {
+                    let offset = 32 * (i as i32 % 10 + 1);
+                    Rectangle::from_loc_and_size(
+                        (area.loc.x + offset, area.loc.y + offset),
+                        (area.size.w / 2, area.size.h / 2),
+                    )
+                });
+                Placement {
+                    window: window.clone(),
+                    geometry,
+                    states: vec![],
+                }
+            })
+            .collect()
+    }
+
+    fn is_floating(&self) -> bool {
+        true
+    }
+
+    fn window_moved(&mut self, window: &W, geometry: Rectangle<i32, Logical>) {
+        self.geometry.insert(window.clone(), geometry);
+    }
+
+    fn remove(&mut self, window: &W) {
+        self.geometry.remove(window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    fn area() -> Rectangle<i32, Logical> {
+        rect(0, 0, 1000, 600)
+    }
+
+    fn geometries(plan: Vec<Placement<u32>>) -> Vec<(u32, Rectangle<i32, Logical>)> {
+        plan.into_iter().map(|p| (p.window, p.geometry)).collect()
+    }
+
+    #[test]
+    fn monocle_shows_focused_or_last() {
+        let mut layout = MonocleLayout::<u32>::default();
+
+        let plan = layout.layout(&[1, 2, 3], None, area());
+        assert_eq!(
+            plan,
+            vec![Placement {
+                window: 3,
+                geometry: area(),
+                states: TILED.to_vec(),
+            }]
+        );
+
+        assert!(layout.focus_changed(&2));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&2), area())),
+            vec![(2, area())]
+        );
+        assert!(!layout.focus_changed(&2));
+
+        // Focus outside the layout keeps the last shown window
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&9), area())),
+            vec![(2, area())]
+        );
+
+        layout.remove(&2);
+        assert_eq!(
+            geometries(layout.layout(&[1, 3], None, area())),
+            vec![(3, area())]
+        );
+        assert!(layout.layout(&[], None, area()).is_empty());
+    }
+
+    #[test]
+    fn master_stack_adds_new_windows_as_master() {
+        let mut layout = MasterStackLayout::<u32>::default();
+
+        assert_eq!(
+            geometries(layout.layout(&[1], None, area())),
+            vec![(1, area())]
+        );
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], None, area())),
+            vec![(2, rect(0, 0, 550, 600)), (1, rect(550, 0, 450, 600))]
+        );
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], None, area())),
+            vec![
+                (3, rect(0, 0, 550, 600)),
+                (2, rect(550, 0, 450, 300)),
+                (1, rect(550, 300, 450, 300)),
+            ]
+        );
+
+        layout.remove(&3);
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], None, area())),
+            vec![(2, rect(0, 0, 550, 600)), (1, rect(550, 0, 450, 600))]
+        );
+    }
+
+    #[test]
+    fn master_stack_keeps_place_of_hidden_windows() {
+        let mut layout = MasterStackLayout::<u32>::default();
+        layout.layout(&[1, 2, 3], None, area());
+
+        assert_eq!(
+            geometries(layout.layout(&[1], None, area())),
+            vec![(1, area())]
+        );
+        // Shown again in the same order, not as new masters
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], None, area())),
+            vec![
+                (3, rect(0, 0, 550, 600)),
+                (2, rect(550, 0, 450, 300)),
+                (1, rect(550, 300, 450, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn master_stack_count_and_ratio() {
+        let mut layout = MasterStackLayout::<u32>::default();
+        layout.layout(&[1, 2, 3], None, area());
+
+        assert!(layout.message(LayoutMessage::MasterCount(1), None));
+        assert_eq!(layout.master_count(), 2);
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], None, area())),
+            vec![
+                (3, rect(0, 0, 550, 300)),
+                (2, rect(0, 300, 550, 300)),
+                (1, rect(550, 0, 450, 600)),
+            ]
+        );
+
+        // Without masters everything is stacked over the whole width
+        assert!(layout.message(LayoutMessage::MasterCount(-5), None));
+        assert_eq!(layout.master_count(), 0);
+        assert!(!layout.message(LayoutMessage::MasterCount(-1), None));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], None, area())),
+            vec![
+                (3, rect(0, 0, 1000, 200)),
+                (2, rect(0, 200, 1000, 200)),
+                (1, rect(0, 400, 1000, 200)),
+            ]
+        );
+
+        assert!(layout.message(LayoutMessage::MasterCount(1), None));
+        assert!(layout.message(LayoutMessage::MasterRatio(0.2), None));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], None, area())),
+            vec![
+                (3, rect(0, 0, 750, 600)),
+                (2, rect(750, 0, 250, 300)),
+                (1, rect(750, 300, 250, 300)),
+            ]
+        );
+
+        assert!(layout.message(LayoutMessage::MasterRatio(1.0), None));
+        assert_eq!(layout.master_ratio(), 0.95);
+        assert!(!layout.message(LayoutMessage::MasterRatio(1.0), None));
+        assert!(!layout.message(LayoutMessage::ColumnWidth(0.1), None));
+    }
+
+    #[test]
+    fn dwindle_splits_focused_node() {
+        let mut layout = DwindleLayout::<u32>::default();
+
+        assert_eq!(
+            geometries(layout.layout(&[1], None, area())),
+            vec![(1, area())]
+        );
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], Some(&1), area())),
+            vec![(1, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]
+        );
+        // The focused node is split along its longer side
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&1), area())),
+            vec![
+                (1, rect(0, 0, 500, 300)),
+                (3, rect(0, 300, 500, 300)),
+                (2, rect(500, 0, 500, 600)),
+            ]
+        );
+
+        // Focus changes don't need a relayout, but are used for the next split
+        assert!(!layout.focus_changed(&2));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3, 4], None, area())),
+            vec![
+                (1, rect(0, 0, 500, 300)),
+                (3, rect(0, 300, 500, 300)),
+                (2, rect(500, 0, 500, 300)),
+                (4, rect(500, 300, 500, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn dwindle_remove_and_hide() {
+        let mut layout = DwindleLayout::<u32>::default();
+        layout.layout(&[1], None, area());
+        layout.layout(&[1, 2], Some(&1), area());
+        layout.layout(&[1, 2, 3], Some(&1), area());
+
+        // Hidden windows give their space to their sibling until shown again
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], Some(&1), area())),
+            vec![(1, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]
+        );
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&1), area())),
+            vec![
+                (1, rect(0, 0, 500, 300)),
+                (3, rect(0, 300, 500, 300)),
+                (2, rect(500, 0, 500, 600)),
+            ]
+        );
+
+        layout.remove(&1);
+        assert_eq!(
+            geometries(layout.layout(&[2, 3], None, area())),
+            vec![(3, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]
+        );
+        layout.remove(&2);
+        layout.remove(&3);
+        assert!(layout.layout(&[], None, area()).is_empty());
+    }
+
+    #[test]
+    fn scrolling_keeps_focused_column_visible() {
+        let mut layout = ScrollingLayout::<u32>::default();
+
+        assert_eq!(
+            geometries(layout.layout(&[1], None, area())),
+            vec![(1, rect(0, 0, 500, 600))]
+        );
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], Some(&1), area())),
+            vec![(1, rect(0, 0, 500, 600)), (2, rect(500, 0, 500, 600))]
+        );
+
+        // New windows open right of the focused column, which is scrolled into view
+        assert!(layout.focus_changed(&2));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&3), area())),
+            vec![
+                (1, rect(-500, 0, 500, 600)),
+                (2, rect(0, 0, 500, 600)),
+                (3, rect(500, 0, 500, 600)),
+            ]
+        );
+        assert_eq!(layout.scroll_offset(), 500);
+
+        // Only as far as needed
+        layout.layout(&[1, 2, 3], Some(&2), area());
+        assert_eq!(layout.scroll_offset(), 500);
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&1), area())),
+            vec![
+                (1, rect(0, 0, 500, 600)),
+                (2, rect(500, 0, 500, 600)),
+                (3, rect(1000, 0, 500, 600)),
+            ]
+        );
+        assert_eq!(layout.scroll_offset(), 0);
+    }
+
+    #[test]
+    fn scrolling_column_membership() {
+        let mut layout = ScrollingLayout::<u32>::default();
+        layout.layout(&[1], None, area());
+        layout.layout(&[1, 2], Some(&2), area());
+        layout.layout(&[1, 2, 3], Some(&3), area());
+
+        assert!(!layout.message(LayoutMessage::ConsumeIntoColumn, Some(&1)));
+        assert!(layout.message(LayoutMessage::ConsumeIntoColumn, Some(&2)));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&2), area())),
+            vec![
+                (1, rect(0, 0, 500, 300)),
+                (2, rect(0, 300, 500, 300)),
+                (3, rect(500, 0, 500, 600)),
+            ]
+        );
+
+        assert!(layout.message(LayoutMessage::ExpelFromColumn, Some(&2)));
+        assert!(!layout.message(LayoutMessage::ExpelFromColumn, Some(&2)));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2, 3], Some(&1), area())),
+            vec![
+                (1, rect(0, 0, 500, 600)),
+                (2, rect(500, 0, 500, 600)),
+                (3, rect(1000, 0, 500, 600)),
+            ]
+        );
+
+        assert!(layout.message(LayoutMessage::ColumnWidth(0.5), None));
+        assert!(!layout.message(LayoutMessage::ColumnWidth(0.5), None));
+        layout.remove(&2);
+        assert_eq!(
+            geometries(layout.layout(&[1, 3], Some(&1), area())),
+            vec![(1, rect(0, 0, 1000, 600)), (3, rect(1000, 0, 1000, 600))]
+        );
+        assert!(!layout.message(LayoutMessage::MasterCount(1), Some(&1)));
+    }
+
+    #[test]
+    fn floating_cascades_and_remembers_moves() {
+        let mut layout = FloatingLayout::<u32>::default();
+        assert!(layout.is_floating());
+
+        let plan = layout.layout(&[1, 2], None, area());
+        assert!(plan.iter().all(|p| p.states.is_empty()));
+        assert_eq!(
+            geometries(plan),
+            vec![(1, rect(32, 32, 500, 300)), (2, rect(64, 64, 500, 300))]
+        );
+
+        layout.window_moved(&1, rect(100, 100, 400, 200));
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], None, area())),
+            vec![(1, rect(100, 100, 400, 200)), (2, rect(64, 64, 500, 300))]
+        );
 
-        let top = space.elements().last().unwrap().clone();
-        top.set_activated(true);
-        Some(top.toplevel().clone())
+        // Hidden windows keep their geometry, removed ones start over
+        layout.layout(&[2], None, area());
+        assert_eq!(
+            geometries(layout.layout(&[1, 2], None, area())),
+            vec![(1, rect(100, 100, 400, 200)), (2, rect(64, 64, 500, 300))]
+        );
+        layout.remove(&1);
+        assert_eq!(
+            geometries(layout.layout(&[2, 1], None, area())),
+            vec![(2, rect(64, 64, 500, 300)), (1, rect(64, 64, 500, 300))]
+        );
     }
 }